@@ -63,8 +63,8 @@ impl<U: Clone + Debug, T: Cycle<U>> Cycle<Vec<U>> for VCycle<U, T> {
     fn new(max: Vec<U>, limit: bool) -> Self {
         let mut state: Vec<T> = max.iter().map(|x| T::new(x.clone(), true)).collect();
         let mut prev: Vec<U> = Vec::new();
-        for i in 0..state.len() {
-            prev.push(state[i].next().unwrap().clone());
+        for s in state.iter_mut() {
+            prev.push(s.next().unwrap().clone());
         }
         return VCycle {
             max,
@@ -1,9 +1,11 @@
+use super::nfa::NFA;
 use core::fmt::Debug;
-use core::hash::Hash;
+use core::hash::{Hash, Hasher};
+use num::{BigUint, Zero};
 use rustc_hash::FxHashMap;
 
 /// A DFA, or deterministic finite automaton.
-#[derive(Debug, PartialEq, Eq)]
+#[derive(Clone, Debug, PartialEq, Eq)]
 pub struct DFA<T: Copy + Eq + Hash> {
     /// The alphabet of the DFA.
     pub alphabet: Vec<T>,
@@ -20,7 +22,7 @@ impl<T: Copy + Eq + Hash + Debug> DFA<T> {
     pub fn new(alphabet: Vec<T>, transition: Vec<Vec<(T, usize)>>, accept: Vec<bool>) -> Self {
         let transition = transition
             .iter()
-            .map(|x| x.into_iter().map(|&x| x.clone()).collect())
+            .map(|x| x.iter().copied().collect())
             .collect();
         DFA {
             alphabet,
@@ -29,36 +31,109 @@ impl<T: Copy + Eq + Hash + Debug> DFA<T> {
         }
     }
 
+    /// Returns the state reached after reading a word, or `None` if a
+    /// transition is missing.
+    pub fn run(&self, word: &[T]) -> Option<usize> {
+        let mut state = 0;
+        for l in word {
+            state = *self.transition[state].get(l)?;
+        }
+        return Some(state);
+    }
+
+    /// Check whether the DFA accepts a word.
+    pub fn accepts(&self, word: &[T]) -> bool {
+        return match self.run(word) {
+            Some(state) => self.accept[state],
+            None => false,
+        };
+    }
+
+    /// Iterate over the accepted words of length at most `max_len`, in
+    /// shortlex order following the order of `alphabet`.
+    ///
+    /// Only words which can still be completed to an accepted word of the
+    /// current length are explored.
+    pub fn words(&self, max_len: usize) -> Words<'_, T> {
+        let mut live = vec![self.accept.clone()];
+        for r in 0..max_len {
+            let next = self
+                .transition
+                .iter()
+                .map(|nt| nt.values().any(|&s| live[r][s]))
+                .collect();
+            live.push(next);
+        }
+        return Words {
+            dfa: self,
+            live,
+            next_length: 0,
+            stack: Vec::new(),
+            word: Vec::new(),
+        };
+    }
+
+    /// Returns the number of accepted words of each length up to `max_len`.
+    pub fn counts(&self, max_len: usize) -> Vec<BigUint> {
+        let mut paths = vec![BigUint::zero(); self.transition.len()];
+        paths[0] = BigUint::from(1u8);
+        let mut res = Vec::new();
+        for length in 0..=max_len {
+            let mut total = BigUint::zero();
+            for (s, p) in paths.iter().enumerate() {
+                if self.accept[s] {
+                    total += p;
+                }
+            }
+            res.push(total);
+            if length == max_len {
+                break;
+            }
+            let mut next = vec![BigUint::zero(); self.transition.len()];
+            for (s1, p) in paths.iter().enumerate() {
+                if p.is_zero() {
+                    continue;
+                }
+                for &s2 in self.transition[s1].values() {
+                    next[s2] += p;
+                }
+            }
+            paths = next;
+        }
+        return res;
+    }
+
     fn prune(&self) -> Self {
-        let mut marked: Vec<usize> = vec![0];
+        let mut marked = vec![false; self.transition.len()];
         let mut frontier: Vec<usize> = vec![0];
-        while frontier.len() > 0 {
+        marked[0] = true;
+        while !frontier.is_empty() {
             let mut new_frontier = Vec::new();
             for &s1 in &frontier {
-                for (_, &s2) in &self.transition[s1] {
-                    if !marked.contains(&s2) {
-                        marked.push(s2);
+                for &s2 in self.transition[s1].values() {
+                    if !marked[s2] {
+                        marked[s2] = true;
                         new_frontier.push(s2);
                     }
                 }
             }
             frontier = new_frontier;
         }
-        let mut states_a = FxHashMap::default();
+        let mut states_a = vec![usize::MAX; self.transition.len()];
         let mut c = 0;
-        for state in 0..self.transition.len() {
-            if marked.contains(&state) {
-                states_a.insert(state, c);
+        for (state, a) in states_a.iter_mut().enumerate() {
+            if marked[state] {
+                *a = c;
                 c += 1;
             }
         }
         let mut new_transition: Vec<FxHashMap<T, usize>> = Vec::new();
         let mut new_accept = Vec::new();
-        for state in 0..self.transition.len() {
-            if states_a.contains_key(&state) {
+        for (state, transition) in self.transition.iter().enumerate() {
+            if marked[state] {
                 let mut nt = FxHashMap::default();
-                for (&l, &r) in &self.transition[state] {
-                    nt.insert(l, states_a[&r]);
+                for (&l, &r) in transition {
+                    nt.insert(l, states_a[r]);
                 }
                 new_transition.push(nt);
                 new_accept.push(self.accept[state]);
@@ -73,76 +148,108 @@ impl<T: Copy + Eq + Hash + Debug> DFA<T> {
 
     /// Returns the minimal equivalent DFA.
     ///
-    /// At least O(n^2) time in the number of states.
-    /// Does not canonicalize the resulting DFA: to check equality you must also find an isomorphism.
+    /// Uses Hopcroft's partition refinement, in O(kn log n) time for n states
+    /// and k symbols. Missing transitions are treated as leading to a dead
+    /// state, which is not added to the result. Each state of the result is
+    /// the least equivalent state of the input, in the input's order.
+    /// Does not canonicalize the resulting DFA: to check equality use
+    /// [`DFA::canonicalize`] instead.
     pub fn minimize(&self) -> Self {
         let _s = self.prune();
-        let mut marked = Vec::new();
-        let mut unmarked = Vec::new();
-        for i in 0.._s.transition.len() {
-            for j in i + 1.._s.transition.len() {
-                if _s.accept[i] == _s.accept[j] {
-                    unmarked.push((i, j));
-                } else {
-                    marked.push((i, j));
+        let n = _s.transition.len();
+        let k = _s.alphabet.len();
+
+        // Complete the transition function with a dead state `n`, and index
+        // its inverse by symbol and target.
+        let total = n + 1;
+        let mut delta = vec![n; total * k];
+        for (state, nt) in _s.transition.iter().enumerate() {
+            for (c, sym) in _s.alphabet.iter().enumerate() {
+                if let Some(&t) = nt.get(sym) {
+                    delta[state * k + c] = t;
                 }
             }
         }
-        loop {
-            let mut new_unmarked = Vec::new();
-            for (i, j) in unmarked.clone() {
-                let mut found = false;
-                for sym in _s.transition[i].keys() {
-                    let a = _s.transition[i][sym];
-                    let b = _s.transition[j][sym];
-                    if marked.contains(&(a, b)) || marked.contains(&(b, a)) {
-                        marked.push((i, j));
-                        found = true;
-                        break;
-                    }
-                }
-                if !found {
-                    new_unmarked.push((i, j))
-                }
+        let mut inverse_start = vec![0; total * k + 1];
+        for state in 0..total {
+            for c in 0..k {
+                inverse_start[c * total + delta[state * k + c] + 1] += 1;
             }
-            if unmarked == new_unmarked {
-                break;
-            } else {
-                unmarked = new_unmarked;
+        }
+        for i in 0..total * k {
+            inverse_start[i + 1] += inverse_start[i];
+        }
+        let mut inverse = vec![0; total * k];
+        let mut fill = inverse_start.clone();
+        for state in 0..total {
+            for c in 0..k {
+                let key = c * total + delta[state * k + c];
+                inverse[fill[key]] = state;
+                fill[key] += 1;
             }
         }
-        let mut states_a: FxHashMap<usize, usize> = FxHashMap::default();
-        for (i, j) in unmarked {
-            if !states_a.contains_key(&j) {
-                states_a.insert(j, i);
-            } else if states_a[&j] > i {
-                states_a.insert(j, i);
+
+        let mut partition = Partition::new(total);
+        for state in 0..n {
+            if _s.accept[state] {
+                partition.mark(state);
             }
         }
-        let mut states_b: FxHashMap<usize, usize> = FxHashMap::default();
-        let mut c = 0;
-        for state in 0.._s.transition.len() {
-            if !states_a.contains_key(&state) {
-                states_a.insert(state, state);
-                states_b.insert(state, c);
-                c += 1;
+        partition.split();
+        let mut waiting: Vec<(usize, usize)> = Vec::new();
+        let mut in_waiting: Vec<bool> = Vec::new();
+        for b in 0..partition.first.len() {
+            for c in 0..k {
+                waiting.push((b, c));
+                in_waiting.push(true);
             }
         }
-        let mut states_c: FxHashMap<usize, usize> = FxHashMap::default();
-        for state in 0.._s.transition.len() {
-            states_c.insert(state, states_b[&states_a[&state]]);
+        while let Some((b, c)) = waiting.pop() {
+            in_waiting[b * k + c] = false;
+            let splitter: Vec<usize> =
+                partition.elements[partition.first[b]..partition.end[b]].to_vec();
+            for t in splitter {
+                let key = c * total + t;
+                for &state in &inverse[inverse_start[key]..inverse_start[key + 1]] {
+                    partition.mark(state);
+                }
+            }
+            for (y, z) in partition.split() {
+                in_waiting.extend(vec![false; k]);
+                let smaller = if partition.size(z) <= partition.size(y) {
+                    z
+                } else {
+                    y
+                };
+                for d in 0..k {
+                    let block = if in_waiting[y * k + d] { z } else { smaller };
+                    if !in_waiting[block * k + d] {
+                        in_waiting[block * k + d] = true;
+                        waiting.push((block, d));
+                    }
+                }
+            }
+        }
+
+        // Number the classes of real states in order of their least member.
+        let mut states_b = vec![usize::MAX; partition.first.len()];
+        let mut representatives = Vec::new();
+        for state in 0..n {
+            let b = partition.block[state];
+            if states_b[b] == usize::MAX {
+                states_b[b] = representatives.len();
+                representatives.push(state);
+            }
         }
         let mut new_transition: Vec<FxHashMap<T, usize>> = Vec::new();
         let mut new_accept = Vec::new();
-        for state in 0.._s.transition.len() {
-            if states_b.contains_key(&state) {
-                let mut nt = FxHashMap::default();
-                for (&l, &r) in &_s.transition[state] {
-                    nt.insert(l, states_b[&states_a[&r]]);
-                }
-                new_transition.push(nt);
-                new_accept.push(_s.accept[state]);
+        for &state in &representatives {
+            let mut nt = FxHashMap::default();
+            for (&l, &r) in &_s.transition[state] {
+                nt.insert(l, states_b[partition.block[r]]);
             }
+            new_transition.push(nt);
+            new_accept.push(_s.accept[state]);
         }
         let out = DFA {
             alphabet: _s.alphabet.clone(),
@@ -151,4 +258,465 @@ impl<T: Copy + Eq + Hash + Debug> DFA<T> {
         };
         return out;
     }
+
+    /// Returns the minimal equivalent DFA without a dead state, by
+    /// Brzozowski's algorithm: reversing and determinizing twice.
+    ///
+    /// Exponential in the worst case, but often fast in practice.
+    pub fn brzozowski_minimize(&self) -> Self {
+        return NFA::from_dfa(&NFA::from_dfa(self).reverse().determinize())
+            .reverse()
+            .determinize();
+    }
+
+    /// Returns which states can reach an accept state.
+    fn live(&self) -> Vec<bool> {
+        let mut inverse = vec![Vec::new(); self.transition.len()];
+        for (s1, nt) in self.transition.iter().enumerate() {
+            for &s2 in nt.values() {
+                inverse[s2].push(s1);
+            }
+        }
+        let mut live = self.accept.clone();
+        let mut frontier: Vec<usize> = (0..live.len()).filter(|&s| live[s]).collect();
+        while let Some(s2) = frontier.pop() {
+            for &s1 in &inverse[s2] {
+                if !live[s1] {
+                    live[s1] = true;
+                    frontier.push(s1);
+                }
+            }
+        }
+        return live;
+    }
+
+    /// Returns the canonical form of the DFA.
+    ///
+    /// This is the minimal DFA without a dead state, whose states are
+    /// numbered in breadth-first order from the start, following the order of
+    /// `alphabet`. Two DFAs with the same alphabet vector are canonicalized to
+    /// equal DFAs exactly when they accept the same language.
+    pub fn canonicalize(&self) -> Self {
+        let min = self.minimize();
+        let live = min.live();
+        let mut states_a = vec![usize::MAX; min.transition.len()];
+        let mut order = vec![0];
+        states_a[0] = 0;
+        let mut next = 0;
+        while next < order.len() {
+            for l in &min.alphabet {
+                if let Some(&s2) = min.transition[order[next]].get(l) {
+                    if live[s2] && states_a[s2] == usize::MAX {
+                        states_a[s2] = order.len();
+                        order.push(s2);
+                    }
+                }
+            }
+            next += 1;
+        }
+        let mut new_transition: Vec<FxHashMap<T, usize>> = Vec::new();
+        let mut new_accept = Vec::new();
+        for &state in &order {
+            let mut nt = FxHashMap::default();
+            for (&l, &r) in &min.transition[state] {
+                if live[r] {
+                    nt.insert(l, states_a[r]);
+                }
+            }
+            new_transition.push(nt);
+            new_accept.push(min.accept[state]);
+        }
+        return DFA {
+            alphabet: min.alphabet,
+            transition: new_transition,
+            accept: new_accept,
+        };
+    }
+
+    /// Returns an equivalent DFA with a transition for every state and symbol.
+    ///
+    /// Missing transitions are sent to a new rejecting sink, which is only
+    /// added if some transition is missing.
+    pub fn complete(&self) -> Self {
+        let sink = self.transition.len();
+        let mut res = self.clone();
+        let mut missing = false;
+        for nt in res.transition.iter_mut() {
+            for &l in &self.alphabet {
+                nt.entry(l).or_insert_with(|| {
+                    missing = true;
+                    sink
+                });
+            }
+        }
+        if missing {
+            res.transition
+                .push(self.alphabet.iter().map(|&l| (l, sink)).collect());
+            res.accept.push(false);
+        }
+        return res;
+    }
+
+    /// Returns a DFA accepting exactly the words rejected by this one.
+    pub fn complement(&self) -> Self {
+        let mut res = self.complete();
+        for a in res.accept.iter_mut() {
+            *a = !*a;
+        }
+        return res;
+    }
+
+    /// Build the product of two DFAs on the same alphabet, restricted to the
+    /// pairs of states reachable from the start. A pair is accepting when `f`
+    /// holds of the acceptance of its two states.
+    fn product(&self, other: &Self, f: impl Fn(bool, bool) -> bool) -> Self {
+        assert_eq!(self.alphabet, other.alphabet);
+        let (a, b) = (self.complete(), other.complete());
+        let m = b.transition.len();
+        let mut index = vec![usize::MAX; a.transition.len() * m];
+        let mut pairs = vec![(0, 0)];
+        index[0] = 0;
+        let mut transition = Vec::new();
+        let mut accept = Vec::new();
+        let mut next = 0;
+        while next < pairs.len() {
+            let (i, j) = pairs[next];
+            let mut nt = FxHashMap::default();
+            for l in &self.alphabet {
+                let key = a.transition[i][l] * m + b.transition[j][l];
+                if index[key] == usize::MAX {
+                    index[key] = pairs.len();
+                    pairs.push((key / m, key % m));
+                }
+                nt.insert(*l, index[key]);
+            }
+            transition.push(nt);
+            accept.push(f(a.accept[i], b.accept[j]));
+            next += 1;
+        }
+        return DFA {
+            alphabet: self.alphabet.clone(),
+            transition,
+            accept,
+        };
+    }
+
+    /// Returns a DFA accepting the words accepted by both DFAs.
+    pub fn intersection(&self, other: &Self) -> Self {
+        return self.product(other, |x, y| x && y);
+    }
+
+    /// Returns a DFA accepting the words accepted by either DFA.
+    pub fn union(&self, other: &Self) -> Self {
+        return self.product(other, |x, y| x || y);
+    }
+
+    /// Returns a DFA accepting the words accepted by `self` but not `other`.
+    pub fn difference(&self, other: &Self) -> Self {
+        return self.product(other, |x, y| x && !y);
+    }
+
+    /// Returns the shortest accepted word, first in the order of `alphabet`
+    /// among those of its length, or `None` if the language is empty.
+    pub fn shortest_word(&self) -> Option<Vec<T>> {
+        let mut parent = vec![None; self.transition.len()];
+        let mut marked = vec![false; self.transition.len()];
+        let mut queue = vec![0];
+        marked[0] = true;
+        let mut next = 0;
+        while next < queue.len() {
+            let s1 = queue[next];
+            if self.accept[s1] {
+                let mut word = Vec::new();
+                let mut s = s1;
+                while let Some((s0, l)) = parent[s] {
+                    word.push(l);
+                    s = s0;
+                }
+                word.reverse();
+                return Some(word);
+            }
+            for &l in &self.alphabet {
+                if let Some(&s2) = self.transition[s1].get(&l) {
+                    if !marked[s2] {
+                        marked[s2] = true;
+                        parent[s2] = Some((s1, l));
+                        queue.push(s2);
+                    }
+                }
+            }
+            next += 1;
+        }
+        return None;
+    }
+
+    /// Check whether the DFA accepts no word, or return the shortest word it
+    /// accepts.
+    pub fn check_empty(&self) -> Result<(), Vec<T>> {
+        return match self.shortest_word() {
+            None => Ok(()),
+            Some(word) => Err(word),
+        };
+    }
+
+    /// Check whether the DFA accepts every word, or return the shortest word
+    /// it rejects.
+    pub fn check_universal(&self) -> Result<(), Vec<T>> {
+        return self.complement().check_empty();
+    }
+
+    /// Check whether every word accepted by `self` is accepted by `other`, or
+    /// return the shortest word accepted only by `self`.
+    pub fn check_subset(&self, other: &Self) -> Result<(), Vec<T>> {
+        return self.difference(other).check_empty();
+    }
+
+    /// Check whether two DFAs accept the same language, or return the
+    /// shortest word accepted by exactly one of them.
+    pub fn check_equivalent(&self, other: &Self) -> Result<(), Vec<T>> {
+        return self.product(other, |x, y| x != y).check_empty();
+    }
+
+    /// Whether the DFA accepts no word.
+    pub fn is_empty(&self) -> bool {
+        return self.check_empty().is_ok();
+    }
+
+    /// Whether the DFA accepts every word.
+    pub fn is_universal(&self) -> bool {
+        return self.check_universal().is_ok();
+    }
+
+    /// Whether every word accepted by `self` is accepted by `other`.
+    pub fn is_subset(&self, other: &Self) -> bool {
+        return self.check_subset(other).is_ok();
+    }
+
+    /// Whether two DFAs accept the same language.
+    pub fn is_equivalent(&self, other: &Self) -> bool {
+        return self.check_equivalent(other).is_ok();
+    }
+}
+
+/// An iterator over the accepted words of a DFA in shortlex order, created
+/// by [`DFA::words`].
+pub struct Words<'a, T: Copy + Eq + Hash> {
+    dfa: &'a DFA<T>,
+    /// Whether each state can reach an accept state in exactly `r` steps.
+    live: Vec<Vec<bool>>,
+    next_length: usize,
+    /// The states along the current word, with the index in the alphabet of
+    /// the next symbol to try from each.
+    stack: Vec<(usize, usize)>,
+    word: Vec<T>,
+}
+
+impl<'a, T: Copy + Eq + Hash> Iterator for Words<'a, T> {
+    type Item = Vec<T>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        loop {
+            let (s1, i) = match self.stack.last_mut() {
+                Some(top) => {
+                    top.1 += 1;
+                    (top.0, top.1 - 1)
+                }
+                None => {
+                    if self.next_length == self.live.len() {
+                        return None;
+                    }
+                    if self.live[self.next_length][0] {
+                        self.stack.push((0, 0));
+                    }
+                    self.next_length += 1;
+                    continue;
+                }
+            };
+            let length = self.next_length - 1;
+            if self.word.len() == length {
+                let res = self.word.clone();
+                self.stack.pop();
+                self.word.pop();
+                return Some(res);
+            }
+            if i == self.dfa.alphabet.len() {
+                self.stack.pop();
+                self.word.pop();
+                continue;
+            }
+            let l = self.dfa.alphabet[i];
+            if let Some(&s2) = self.dfa.transition[s1].get(&l) {
+                if self.live[length - self.word.len() - 1][s2] {
+                    self.stack.push((s2, 0));
+                    self.word.push(l);
+                }
+            }
+        }
+    }
+}
+
+impl<T: Copy + Eq + Hash> Hash for DFA<T> {
+    fn hash<H: Hasher>(&self, state: &mut H) {
+        self.alphabet.hash(state);
+        self.accept.hash(state);
+        for nt in &self.transition {
+            for l in &self.alphabet {
+                nt.get(l).hash(state);
+            }
+        }
+    }
+}
+
+/// A partition of `0..n` into blocks that can be refined in time proportional
+/// to the number of elements marked.
+///
+/// The elements of each block are contiguous in `elements`, with the marked
+/// ones first.
+struct Partition {
+    elements: Vec<usize>,
+    location: Vec<usize>,
+    block: Vec<usize>,
+    first: Vec<usize>,
+    end: Vec<usize>,
+    mid: Vec<usize>,
+    touched: Vec<usize>,
+}
+
+impl Partition {
+    fn new(n: usize) -> Self {
+        Partition {
+            elements: (0..n).collect(),
+            location: (0..n).collect(),
+            block: vec![0; n],
+            first: vec![0],
+            end: vec![n],
+            mid: vec![0],
+            touched: Vec::new(),
+        }
+    }
+
+    fn size(&self, b: usize) -> usize {
+        return self.end[b] - self.first[b];
+    }
+
+    fn mark(&mut self, e: usize) {
+        let b = self.block[e];
+        let i = self.location[e];
+        let j = self.mid[b];
+        if i < j {
+            return;
+        }
+        if j == self.first[b] {
+            self.touched.push(b);
+        }
+        self.elements.swap(i, j);
+        self.location[self.elements[i]] = i;
+        self.location[self.elements[j]] = j;
+        self.mid[b] += 1;
+    }
+
+    /// Split every block with both marked and unmarked elements, moving the
+    /// marked ones to a new block. Returns the `(old, new)` pairs.
+    fn split(&mut self) -> Vec<(usize, usize)> {
+        let mut res = Vec::new();
+        for b in std::mem::take(&mut self.touched) {
+            if self.mid[b] == self.end[b] {
+                self.mid[b] = self.first[b];
+                continue;
+            }
+            let z = self.first.len();
+            self.first.push(self.first[b]);
+            self.end.push(self.mid[b]);
+            self.mid.push(self.first[b]);
+            self.first[b] = self.mid[b];
+            for i in self.first[z]..self.end[z] {
+                self.block[self.elements[i]] = z;
+            }
+            res.push((b, z));
+        }
+        return res;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// A xorshift generator, so that the tests need no dependencies.
+    fn random(seed: &mut u64) -> usize {
+        *seed ^= *seed << 13;
+        *seed ^= *seed >> 7;
+        *seed ^= *seed << 17;
+        return *seed as usize;
+    }
+
+    fn random_dfa(seed: &mut u64, n: usize, k: u8) -> DFA<u8> {
+        let transition = (0..n)
+            .map(|_| (0..k).map(|l| (l, random(seed) % n)).collect())
+            .collect();
+        let accept = (0..n).map(|_| random(seed) & 1 == 1).collect();
+        return DFA::new((0..k).collect(), transition, accept);
+    }
+
+    /// Minimize a complete DFA by the table-filling algorithm, keeping the
+    /// least state of each class.
+    fn table_filling(dfa: &DFA<u8>) -> DFA<u8> {
+        let s = dfa.prune();
+        let n = s.transition.len();
+        let mut distinct: Vec<Vec<bool>> = (0..n)
+            .map(|i| (0..n).map(|j| s.accept[i] != s.accept[j]).collect())
+            .collect();
+        let mut changed = true;
+        while changed {
+            changed = false;
+            for i in 0..n {
+                for j in 0..n {
+                    if !distinct[i][j]
+                        && s.alphabet
+                            .iter()
+                            .any(|l| distinct[s.transition[i][l]][s.transition[j][l]])
+                    {
+                        distinct[i][j] = true;
+                        changed = true;
+                    }
+                }
+            }
+        }
+        let rep: Vec<usize> = (0..n)
+            .map(|i| (0..n).find(|&j| !distinct[i][j]).unwrap())
+            .collect();
+        let mut index = vec![0; n];
+        let mut c = 0;
+        for i in 0..n {
+            if rep[i] == i {
+                index[i] = c;
+                c += 1;
+            }
+        }
+        let transition = (0..n)
+            .filter(|&i| rep[i] == i)
+            .map(|i| {
+                s.alphabet
+                    .iter()
+                    .map(|&l| (l, index[rep[s.transition[i][&l]]]))
+                    .collect()
+            })
+            .collect();
+        let accept = (0..n)
+            .filter(|&i| rep[i] == i)
+            .map(|i| s.accept[i])
+            .collect();
+        return DFA::new(s.alphabet.clone(), transition, accept);
+    }
+
+    #[test]
+    fn hopcroft_matches_table_filling() {
+        let mut seed = 1;
+        for _ in 0..3000 {
+            let n = 1 + random(&mut seed) % 10;
+            let k = 1 + (random(&mut seed) % 3) as u8;
+            let dfa = random_dfa(&mut seed, n, k);
+            assert_eq!(dfa.minimize(), table_filling(&dfa), "{:?}", dfa);
+        }
+    }
 }
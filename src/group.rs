@@ -0,0 +1,642 @@
+use super::transducer::Transducer;
+use graphviz_rust::dot_generator::*;
+use graphviz_rust::dot_structures::*;
+use rayon::prelude::*;
+use rustc_hash::FxHashMap;
+use rustc_hash::FxHashSet;
+
+/// A letter of a word over a generating set: the index of a generator, and
+/// whether it appears inverted.
+pub type Letter = (usize, bool);
+
+/// The answer to an instance of the word problem, together with its proof.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum Verdict {
+    /// The word is trivial. The minimized product is the one-state identity.
+    Trivial(Transducer),
+    /// The word is nontrivial. The minimized product changes the given word,
+    /// and no shorter one.
+    Nontrivial(Transducer, Vec<u8>),
+}
+
+/// Freely reduce a word, cancelling each generator next to its inverse.
+pub fn reduce(word: &[Letter]) -> Vec<Letter> {
+    let mut res: Vec<Letter> = Vec::new();
+    for &(g, inv) in word {
+        if res.last() == Some(&(g, !inv)) {
+            res.pop();
+        } else {
+            res.push((g, inv));
+        }
+    }
+    return res;
+}
+
+/// A solver for the word problem in the group generated by some invertible
+/// transducers.
+///
+/// Words act from left to right: the first letter is applied first.
+#[derive(Clone, Debug)]
+pub struct WordProblem {
+    generators: Vec<Transducer>,
+    inverses: Vec<Transducer>,
+}
+
+impl WordProblem {
+    /// Create a solver for the group generated by `generators`, which must
+    /// share an alphabet. There must be at least one generator.
+    pub fn new(generators: &[Transducer]) -> Self {
+        assert!(!generators.is_empty(), "no generators given.");
+        let generators: Vec<_> = generators.iter().map(|g| g.minimize()).collect();
+        let inverses = generators.iter().map(|g| g.inverse()).collect();
+        WordProblem {
+            generators,
+            inverses,
+        }
+    }
+
+    /// Compute the minimized transducer represented by `word`.
+    ///
+    /// The word is freely reduced first, and the product is minimized after
+    /// every letter, so intermediate transducers stay as small as the elements
+    /// they represent.
+    pub fn evaluate(&self, word: &[Letter]) -> Transducer {
+        let mut acc = Transducer::identity(self.generators[0].alphabet());
+        for (g, inv) in reduce(word) {
+            if inv {
+                acc = acc.product(&self.inverses[g]);
+            } else {
+                acc = acc.product(&self.generators[g]);
+            }
+        }
+        return acc;
+    }
+
+    /// Decide whether `word` represents the identity.
+    pub fn solve(&self, word: &[Letter]) -> Verdict {
+        let res = self.evaluate(word);
+        match res.witness() {
+            None => Verdict::Trivial(res),
+            Some(w) => Verdict::Nontrivial(res, w),
+        }
+    }
+}
+
+/// Limits on the work done by procedures that may not terminate.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct Budget {
+    /// The maximum number of distinct elements to generate.
+    pub elements: usize,
+    /// The maximum number of states of any minimized element.
+    pub states: usize,
+}
+
+impl Default for Budget {
+    fn default() -> Self {
+        Budget {
+            elements: 10000,
+            states: 64,
+        }
+    }
+}
+
+/// The order of a group or of an element.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Order {
+    /// The order is finite.
+    Finite(usize),
+    /// The order is infinite.
+    Infinite,
+    /// The budget ran out before the order was determined.
+    Unknown,
+}
+
+/// Compute the order of the group generated by `generators`.
+///
+/// Products with the generators are taken and minimized until the set of
+/// elements closes. Strictly, this is the semigroup generated, which is the
+/// whole group whenever the group is finite; for non-invertible generators it
+/// gives the order of the automaton semigroup instead. There must be at
+/// least one generator.
+pub fn group_order(generators: &[Transducer], budget: &Budget) -> Order {
+    assert!(!generators.is_empty(), "no generators given.");
+    let generators: Vec<_> = generators.iter().map(|g| g.minimize()).collect();
+    let mut seen: FxHashSet<Transducer> = generators.iter().cloned().collect();
+    let mut frontier: Vec<Transducer> = seen.iter().cloned().collect();
+    while !frontier.is_empty() {
+        let products: Vec<Transducer> = frontier
+            .par_iter()
+            .flat_map(|x| generators.par_iter().map(move |g| x.product(g)))
+            .collect();
+        let mut new_frontier = Vec::new();
+        for p in products {
+            if p.len() > budget.states {
+                return Order::Unknown;
+            }
+            if seen.insert(p.clone()) {
+                new_frontier.push(p);
+            }
+        }
+        if seen.len() > budget.elements {
+            return Order::Unknown;
+        }
+        frontier = new_frontier;
+    }
+    return Order::Finite(seen.len());
+}
+
+/// The result of a successful contraction test.
+#[derive(Clone, Debug, PartialEq)]
+pub struct Contraction {
+    /// The nucleus, with one state per element. Each state's transitions lead
+    /// to its sections, so the nucleus is closed under taking sections.
+    pub nucleus: Transducer,
+    /// The number of levels after which every section of a product of two
+    /// nucleus elements lies in the nucleus.
+    pub level: usize,
+    /// The estimate `2^(-1/level)` of the contraction coefficient, or `0` if
+    /// the nucleus is closed under products.
+    ///
+    /// Split a product of `n` nucleus elements into `⌈n/2⌉` products of
+    /// pairs. The sections of each pair on level `level` lie in the nucleus,
+    /// and so do all their sections below, since the nucleus is closed under
+    /// sections. So on level `level` and below, the sections of the product
+    /// are products of at most `⌈n/2⌉` nucleus elements: lengths halve, up to
+    /// an additive constant, every `level` levels.
+    pub coefficient: f64,
+}
+
+/// Explore every section of `start`, returning the graph of sections, or
+/// `None` if the budget runs out.
+fn section_graph(
+    start: &[Transducer],
+    budget: &Budget,
+) -> Option<(Vec<Transducer>, Vec<Vec<usize>>)> {
+    let mut index = FxHashMap::default();
+    let mut nodes = Vec::new();
+    for x in start {
+        if !index.contains_key(x) {
+            index.insert(x.clone(), nodes.len());
+            nodes.push(x.clone());
+        }
+    }
+    let mut edges = Vec::new();
+    while edges.len() < nodes.len() {
+        let mut out = Vec::new();
+        for s in nodes[edges.len()].sections() {
+            if s.len() > budget.states {
+                return None;
+            }
+            let next = *index.entry(s.clone()).or_insert(nodes.len());
+            if next == nodes.len() {
+                nodes.push(s);
+            }
+            out.push(next);
+        }
+        edges.push(out);
+        if nodes.len() > budget.elements {
+            return None;
+        }
+    }
+    return Some((nodes, edges));
+}
+
+/// Find every element lying on a cycle of sections below `start`, together
+/// with all of their sections.
+fn cyclic_part(start: &[Transducer], budget: &Budget) -> Option<FxHashSet<Transducer>> {
+    let (nodes, edges) = section_graph(start, budget)?;
+    let mut reverse = vec![Vec::new(); nodes.len()];
+    for (u, out) in edges.iter().enumerate() {
+        for &v in out {
+            reverse[v].push(u);
+        }
+    }
+
+    // Kosaraju's algorithm: order by finishing time, then collect components
+    // of the reversed graph.
+    let mut finished = Vec::new();
+    let mut seen = vec![false; nodes.len()];
+    for root in 0..nodes.len() {
+        if seen[root] {
+            continue;
+        }
+        seen[root] = true;
+        let mut stack = vec![(root, 0)];
+        while let Some((u, i)) = stack.pop() {
+            if i < edges[u].len() {
+                stack.push((u, i + 1));
+                let v = edges[u][i];
+                if !seen[v] {
+                    seen[v] = true;
+                    stack.push((v, 0));
+                }
+            } else {
+                finished.push(u);
+            }
+        }
+    }
+    let mut component = vec![usize::MAX; nodes.len()];
+    let mut sizes = Vec::new();
+    for &root in finished.iter().rev() {
+        if component[root] != usize::MAX {
+            continue;
+        }
+        let c = sizes.len();
+        sizes.push(0);
+        component[root] = c;
+        let mut stack = vec![root];
+        while let Some(u) = stack.pop() {
+            sizes[c] += 1;
+            for &v in &reverse[u] {
+                if component[v] == usize::MAX {
+                    component[v] = c;
+                    stack.push(v);
+                }
+            }
+        }
+    }
+
+    let mut frontier: Vec<usize> = (0..nodes.len())
+        .filter(|&u| sizes[component[u]] > 1 || edges[u].contains(&u))
+        .collect();
+    let mut res = vec![false; nodes.len()];
+    for &u in &frontier {
+        res[u] = true;
+    }
+    while let Some(u) = frontier.pop() {
+        for &v in &edges[u] {
+            if !res[v] {
+                res[v] = true;
+                frontier.push(v);
+            }
+        }
+    }
+    return Some(
+        nodes
+            .into_iter()
+            .zip(res)
+            .filter(|(_, r)| *r)
+            .map(|(x, _)| x)
+            .collect(),
+    );
+}
+
+/// The number of levels after which every section of `x` lies in `nucleus`.
+fn fall_depth(
+    x: &Transducer,
+    nucleus: &FxHashSet<Transducer>,
+    depth: &mut FxHashMap<Transducer, usize>,
+) -> usize {
+    if nucleus.contains(x) {
+        return 0;
+    }
+    if let Some(&d) = depth.get(x) {
+        return d;
+    }
+    let d = 1 + x
+        .sections()
+        .iter()
+        .map(|s| fall_depth(s, nucleus, depth))
+        .max()
+        .unwrap();
+    depth.insert(x.clone(), d);
+    return d;
+}
+
+/// Compute the nucleus of the group generated by `generators`, or `None` if
+/// the group is not found to be contracting within the budget.
+///
+/// Starting from the elements on cycles of sections of the generators and
+/// their inverses, this adds the cyclic sections of products of pairs of
+/// nucleus elements until no new ones appear. There must be at least one
+/// generator.
+pub fn nucleus(generators: &[Transducer], budget: &Budget) -> Option<Contraction> {
+    assert!(!generators.is_empty(), "no generators given.");
+    let mut start: Vec<_> = generators.iter().map(|g| g.minimize()).collect();
+    start.extend(generators.iter().map(|g| g.inverse().minimize()));
+    start.push(Transducer::identity(generators[0].alphabet()));
+    let mut nucleus = cyclic_part(&start, budget)?;
+    let products = loop {
+        let elements: Vec<_> = nucleus.iter().cloned().collect();
+        let products: Vec<Transducer> = elements
+            .par_iter()
+            .flat_map(|x| elements.par_iter().map(move |y| x.product(y)))
+            .collect();
+        let new = cyclic_part(&products, budget)?;
+        if new.is_subset(&nucleus) {
+            break products;
+        }
+        nucleus.extend(new);
+        if nucleus.len() > budget.elements {
+            return None;
+        }
+    };
+
+    // Every section of a product outside the nucleus eventually falls into
+    // it, since the remaining sections form an acyclic graph.
+    let mut depth: FxHashMap<Transducer, usize> = FxHashMap::default();
+    let level = products
+        .iter()
+        .map(|p| fall_depth(p, &nucleus, &mut depth))
+        .max()
+        .unwrap();
+    let coefficient = if level == 0 {
+        0.0
+    } else {
+        0.5f64.powf(1.0 / level as f64)
+    };
+
+    let mut elements: Vec<_> = nucleus.into_iter().collect();
+    elements.sort_by_key(|x| (x.len(), x.clone()));
+    let index: FxHashMap<Transducer, usize> = elements
+        .iter()
+        .enumerate()
+        .map(|(i, x)| (x.clone(), i))
+        .collect();
+    let transition = elements
+        .iter()
+        .map(|x| x.sections().iter().map(|s| index[s]).collect())
+        .collect();
+    let output = elements.iter().map(|x| x.root().to_vec()).collect();
+    return Some(Contraction {
+        nucleus: Transducer::with_output(transition, output),
+        level,
+        coefficient,
+    });
+}
+
+/// The Schreier graph of the action of some transducers on one level of the
+/// tree of words.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct SchreierGraph {
+    /// The size `k` of the alphabet.
+    pub alphabet: usize,
+    /// The level, that is, the length of the words acted on.
+    pub level: usize,
+    /// For each vertex, the edges leaving it as `(generator, target)` pairs.
+    /// Vertex `v` is the word whose letters are the base-`k` digits of `v`,
+    /// most significant first.
+    pub adjacency: Vec<Vec<(usize, usize)>>,
+}
+
+/// Create the Schreier graph of the action of `generators` on words of length
+/// `level`. There must be at least one generator.
+pub fn schreier_graph(generators: &[Transducer], level: usize) -> SchreierGraph {
+    assert!(!generators.is_empty(), "no generators given.");
+    let k = generators[0].alphabet();
+    let size = k.pow(level as u32);
+    let adjacency = (0..size)
+        .into_par_iter()
+        .map(|v| {
+            let word = vertex_word(k, level, v);
+            generators
+                .iter()
+                .enumerate()
+                .map(|(i, g)| {
+                    let mut w = word.clone();
+                    g.step(&mut w);
+                    (i, w.iter().fold(0, |acc, &c| acc * k + c as usize))
+                })
+                .collect()
+        })
+        .collect();
+    return SchreierGraph {
+        alphabet: k,
+        level,
+        adjacency,
+    };
+}
+
+fn vertex_word(k: usize, level: usize, v: usize) -> Vec<u8> {
+    let mut word = vec![0; level];
+    let mut v = v;
+    for c in word.iter_mut().rev() {
+        *c = (v % k) as u8;
+        v /= k;
+    }
+    return word;
+}
+
+impl SchreierGraph {
+    /// The word represented by vertex `v`.
+    pub fn word(&self, v: usize) -> Vec<u8> {
+        return vertex_word(self.alphabet, self.level, v);
+    }
+
+    /// The distinct neighbours of each vertex, ignoring edge directions,
+    /// labels, and loops.
+    pub fn neighbours(&self) -> Vec<Vec<usize>> {
+        let mut res: Vec<FxHashSet<usize>> = vec![FxHashSet::default(); self.adjacency.len()];
+        for (u, out) in self.adjacency.iter().enumerate() {
+            for &(_, v) in out {
+                if u != v {
+                    res[u].insert(v);
+                    res[v].insert(u);
+                }
+            }
+        }
+        return res
+            .into_iter()
+            .map(|x| {
+                let mut x: Vec<_> = x.into_iter().collect();
+                x.sort();
+                x
+            })
+            .collect();
+    }
+
+    fn distances(neighbours: &[Vec<usize>], root: usize) -> Vec<usize> {
+        let mut dist = vec![usize::MAX; neighbours.len()];
+        dist[root] = 0;
+        let mut frontier = vec![root];
+        while !frontier.is_empty() {
+            let mut new_frontier = Vec::new();
+            for &u in &frontier {
+                for &v in &neighbours[u] {
+                    if dist[v] == usize::MAX {
+                        dist[v] = dist[u] + 1;
+                        new_frontier.push(v);
+                    }
+                }
+            }
+            frontier = new_frontier;
+        }
+        return dist;
+    }
+
+    /// The number of connected components, that is, of orbits on the level.
+    pub fn components(&self) -> usize {
+        let neighbours = self.neighbours();
+        let mut seen = vec![false; neighbours.len()];
+        let mut res = 0;
+        for root in 0..neighbours.len() {
+            if !seen[root] {
+                res += 1;
+                for (v, d) in Self::distances(&neighbours, root).into_iter().enumerate() {
+                    if d != usize::MAX {
+                        seen[v] = true;
+                    }
+                }
+            }
+        }
+        return res;
+    }
+
+    /// The largest distance between two vertices in the same component.
+    ///
+    /// O(V * E) time.
+    pub fn diameter(&self) -> usize {
+        let neighbours = self.neighbours();
+        return (0..neighbours.len())
+            .into_par_iter()
+            .map(|root| {
+                Self::distances(&neighbours, root)
+                    .into_iter()
+                    .filter(|&d| d != usize::MAX)
+                    .max()
+                    .unwrap()
+            })
+            .max()
+            .unwrap_or(0);
+    }
+
+    /// The number of vertices with each number of distinct neighbours, as
+    /// sorted `(degree, count)` pairs.
+    pub fn degree_distribution(&self) -> Vec<(usize, usize)> {
+        let mut counts: FxHashMap<usize, usize> = FxHashMap::default();
+        for x in self.neighbours() {
+            *counts.entry(x.len()).or_insert(0) += 1;
+        }
+        let mut res: Vec<_> = counts.into_iter().collect();
+        res.sort();
+        return res;
+    }
+
+    /// Create a graph with a node for each word and an edge for each
+    /// generator, labelled by its index.
+    pub fn graph(&self) -> Graph {
+        let mut res = graph!(di id!());
+        for v in 0..self.adjacency.len() {
+            let label: String = self.word(v).iter().map(|x| x.to_string()).collect();
+            res.add_stmt(stmt!(node!(esc label; attr!("shape", "circle"))));
+        }
+        for (u, out) in self.adjacency.iter().enumerate() {
+            let u_label: String = self.word(u).iter().map(|x| x.to_string()).collect();
+            for &(i, v) in out {
+                let v_label: String = self.word(v).iter().map(|x| x.to_string()).collect();
+                res.add_stmt(stmt!(
+                    edge!(node_id!(esc u_label) => node_id!(esc v_label); attr!("label", (format!(" {}", i))))
+                ));
+            }
+        }
+        return res;
+    }
+}
+
+/// An iterator through the sizes `|B(n)|` of the balls of radius `n = 0, 1,
+/// ...` in the group generated by some transducers, with respect to the
+/// generators and their inverses.
+///
+/// Iteration stops once a sphere is empty, in which case the last size is
+/// the order of the group.
+pub struct Growth {
+    alphabet: usize,
+    generators: Vec<Transducer>,
+    ball: FxHashSet<Transducer>,
+    sphere: Vec<Transducer>,
+}
+
+impl Growth {
+    /// Create an iterator through the ball sizes for the group generated by
+    /// `generators`, such as the states returned by
+    /// [`Transducer::generators`]. There must be at least one generator,
+    /// which fixes the alphabet.
+    pub fn new(generators: &[Transducer]) -> Self {
+        assert!(!generators.is_empty(), "no generators given.");
+        let alphabet = generators[0].alphabet();
+        let mut set = FxHashSet::default();
+        for g in generators {
+            set.insert(g.minimize());
+            set.insert(g.inverse().minimize());
+        }
+        let mut generators: Vec<_> = set.into_iter().filter(|g| !g.is_identity()).collect();
+        generators.sort();
+        Growth {
+            alphabet,
+            generators,
+            ball: FxHashSet::default(),
+            sphere: Vec::new(),
+        }
+    }
+}
+
+impl Iterator for Growth {
+    type Item = usize;
+
+    fn next(&mut self) -> Option<usize> {
+        if self.ball.is_empty() {
+            let e = Transducer::identity(self.alphabet);
+            self.ball.insert(e.clone());
+            self.sphere.push(e);
+            return Some(1);
+        }
+        if self.sphere.is_empty() {
+            return None;
+        }
+        let generators = &self.generators;
+        let products: Vec<Transducer> = self
+            .sphere
+            .par_iter()
+            .flat_map(|x| generators.par_iter().map(move |g| x.product(g)))
+            .collect();
+        self.sphere = Vec::new();
+        for p in products {
+            if self.ball.insert(p.clone()) {
+                self.sphere.push(p);
+            }
+        }
+        if self.sphere.is_empty() {
+            return None;
+        }
+        return Some(self.ball.len());
+    }
+}
+
+/// Format ball sizes `|B(0)|, |B(1)|, ...` as the truncated spherical growth
+/// series `Σ |S(n)| z^n`.
+pub fn growth_series(balls: &[usize]) -> String {
+    let mut terms = Vec::new();
+    for (n, &b) in balls.iter().enumerate() {
+        let s = if n == 0 { b } else { b - balls[n - 1] };
+        terms.push(match n {
+            0 => s.to_string(),
+            1 => format!("{}z", s),
+            _ => format!("{}z^{}", s, n),
+        });
+    }
+    return terms.join(" + ");
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn adding_machine_nucleus() {
+        let add = Transducer::new(vec![vec![1, 0], vec![1, 1]], vec![1, 0]);
+        let contraction = nucleus(std::slice::from_ref(&add), &Budget::default()).unwrap();
+        let elements: FxHashSet<Transducer> =
+            contraction.nucleus.generators().into_iter().collect();
+        let expected: FxHashSet<Transducer> = [
+            Transducer::identity(2),
+            add.minimize(),
+            add.inverse().minimize(),
+        ]
+        .into_iter()
+        .collect();
+        assert_eq!(elements, expected);
+        assert_eq!(contraction.nucleus.len(), 3);
+        assert_eq!(contraction.level, 1);
+        assert_eq!(contraction.coefficient, 0.5);
+    }
+}
@@ -1,9 +1,10 @@
 #![warn(missing_docs)]
+#![allow(clippy::needless_return)]
 
 //! Tools for manipulating transducers.
 //!
-//! Currently this program only handles reversible alphabetic transducers on a
-//! binary alphabet.
+//! Currently this program handles alphabetic transducers, or Mealy machines, on
+//! a finite alphabet `0..k`. Orbit computations require them to be reversible.
 
 /// Iterator representations of products and permutations.
 pub mod combi;
@@ -11,9 +12,24 @@ pub mod combi;
 /// Representations of DFAs.
 pub mod dfa;
 
+/// Algorithms on groups generated by transducers.
+pub mod group;
+
+/// Representations of NFAs.
+pub mod nfa;
+
+/// Orbit structure of transducers computed level by level.
+pub mod orbit;
+
+/// Regular expressions and their conversion to and from DFAs.
+pub mod regex;
+
 /// Representations of transducers and DFAs.
 pub mod transducer;
 
+/// Bit-packed binary words.
+pub mod word;
+
 use combi::{Cycle, UCycle, VCycle};
 use graphviz_rust::dot_generator::*;
 use graphviz_rust::dot_structures::*;
@@ -32,7 +48,7 @@ pub fn write_orbit_trees(depth: usize) {
     let mut ctx = PrinterContext::default();
     ctx.always_inline();
     for (i, m) in res.iter().enumerate() {
-        let g = m.into_iter().next().unwrap().orbit_tree(depth);
+        let g = m.iter().next().unwrap().orbit_tree(depth);
         exec(
             g,
             &mut ctx,
@@ -71,6 +87,11 @@ fn is_odd(x: &[usize]) -> bool {
     x.iter().filter(|&&k| k == 0).count() % 2 == 1
 }
 
+#[allow(
+    clippy::if_same_then_else,
+    clippy::needless_late_init,
+    clippy::needless_range_loop
+)]
 fn main() {
     let mut seen = FxHashSet::default();
     for (i, m) in AllTransducers::new().enumerate() {
@@ -97,8 +118,11 @@ fn main() {
             &m.product(&m).product(&m).minimize()
         ]
     );
-    m.detailed_orbit_tree(8);
-    m.product(&m).product(&m).detailed_orbit_tree(8);
+    print!("{}", m.detailed_orbit_tree(8).to_text());
+    print!(
+        "{}",
+        m.product(&m).product(&m).detailed_orbit_tree(8).to_text()
+    );
     let mut g = graph!(strict di id!());
     let width = depth;
     let mut g_even = subgraph!(id!("cluster_s0"));
@@ -195,7 +219,7 @@ fn main() {
             CommandArg::Custom("-Goverlap=prism".to_string()),
             CommandArg::Custom("-Gsplines=ortho".to_string()),
             CommandArg::Format(Format::Png),
-            CommandArg::Output(format!("images/m_interest.png").to_string()),
+            CommandArg::Output("images/m_interest.png".to_string()),
         ],
     )
     .unwrap();
@@ -0,0 +1,210 @@
+use super::dfa::DFA;
+use core::fmt::Debug;
+use core::hash::Hash;
+use rustc_hash::FxHashMap;
+
+/// An NFA, or nondeterministic finite automaton, with epsilon transitions.
+///
+/// As for DFAs, the states are consecutive integers and the start state is 0.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct NFA<T: Copy + Eq + Hash> {
+    /// The alphabet of the NFA.
+    pub alphabet: Vec<T>,
+    /// The transitions out of each state, where `None` is an epsilon
+    /// transition.
+    pub transition: Vec<Vec<(Option<T>, usize)>>,
+    /// The accept set of the NFA represented as a boolean vector.
+    pub accept: Vec<bool>,
+}
+
+impl<T: Copy + Eq + Hash + Debug> NFA<T> {
+    /// Create an NFA with given alphabet, transitions, and accept states.
+    pub fn new(
+        alphabet: Vec<T>,
+        transition: Vec<Vec<(Option<T>, usize)>>,
+        accept: Vec<bool>,
+    ) -> Self {
+        NFA {
+            alphabet,
+            transition,
+            accept,
+        }
+    }
+
+    /// Create an NFA accepting no words.
+    pub fn empty(alphabet: Vec<T>) -> Self {
+        return NFA::new(alphabet, vec![Vec::new()], vec![false]);
+    }
+
+    /// Create an NFA accepting only the empty word.
+    pub fn epsilon(alphabet: Vec<T>) -> Self {
+        return NFA::new(alphabet, vec![Vec::new()], vec![true]);
+    }
+
+    /// Create an NFA accepting only the word of one symbol `l`.
+    pub fn symbol(alphabet: Vec<T>, l: T) -> Self {
+        assert!(alphabet.contains(&l), "{:?} is not in the alphabet.", l);
+        return NFA::new(
+            alphabet,
+            vec![vec![(Some(l), 1)], Vec::new()],
+            vec![false, true],
+        );
+    }
+
+    /// Create an NFA accepting the same language as a DFA.
+    pub fn from_dfa(dfa: &DFA<T>) -> Self {
+        let transition = dfa
+            .transition
+            .iter()
+            .map(|nt| {
+                dfa.alphabet
+                    .iter()
+                    .filter_map(|l| nt.get(l).map(|&s| (Some(*l), s)))
+                    .collect()
+            })
+            .collect();
+        return NFA::new(dfa.alphabet.clone(), transition, dfa.accept.clone());
+    }
+
+    /// Copy the states of `other` after those of `self`, returning the number
+    /// of the first copied state.
+    fn append(&mut self, other: &Self) -> usize {
+        assert_eq!(self.alphabet, other.alphabet);
+        let offset = self.transition.len();
+        for nt in &other.transition {
+            self.transition
+                .push(nt.iter().map(|&(l, s)| (l, s + offset)).collect());
+        }
+        self.accept.extend(&other.accept);
+        return offset;
+    }
+
+    /// Returns an NFA accepting the words accepted by either NFA.
+    pub fn union(&self, other: &Self) -> Self {
+        let mut res = NFA::new(self.alphabet.clone(), vec![Vec::new()], vec![false]);
+        let a = res.append(self);
+        let b = res.append(other);
+        res.transition[0] = vec![(None, a), (None, b)];
+        return res;
+    }
+
+    /// Returns an NFA accepting a word accepted by `self` followed by a word
+    /// accepted by `other`.
+    pub fn concat(&self, other: &Self) -> Self {
+        let mut res = self.clone();
+        let b = res.append(other);
+        for s in 0..b {
+            if res.accept[s] {
+                res.accept[s] = false;
+                res.transition[s].push((None, b));
+            }
+        }
+        return res;
+    }
+
+    /// Returns an NFA accepting the concatenations of any number of words
+    /// accepted by this one.
+    pub fn star(&self) -> Self {
+        let mut res = NFA::new(self.alphabet.clone(), vec![Vec::new()], vec![true]);
+        let a = res.append(self);
+        res.transition[0].push((None, a));
+        for s in a..res.transition.len() {
+            if res.accept[s] {
+                res.transition[s].push((None, 0));
+            }
+        }
+        return res;
+    }
+
+    /// Returns an NFA accepting the reversals of the words accepted by this
+    /// one.
+    pub fn reverse(&self) -> Self {
+        let n = self.transition.len();
+        let mut transition = vec![Vec::new(); n + 1];
+        for (s1, nt) in self.transition.iter().enumerate() {
+            for &(l, s2) in nt {
+                transition[s2 + 1].push((l, s1 + 1));
+            }
+        }
+        transition[0] = (0..n)
+            .filter(|&s| self.accept[s])
+            .map(|s| (None, s + 1))
+            .collect();
+        let mut accept = vec![false; n + 1];
+        accept[1] = true;
+        return NFA::new(self.alphabet.clone(), transition, accept);
+    }
+
+    /// Extend a set of states to its closure under epsilon transitions, and
+    /// keep only the states which accept or have a symbol transition, since
+    /// these determine the behaviour of the set. Returned in increasing order.
+    fn closure(&self, mut states: Vec<usize>, marked: &mut [bool]) -> Vec<usize> {
+        let mut frontier = states.clone();
+        while let Some(s1) = frontier.pop() {
+            for &(l, s2) in &self.transition[s1] {
+                if l.is_none() && !marked[s2] {
+                    marked[s2] = true;
+                    states.push(s2);
+                    frontier.push(s2);
+                }
+            }
+        }
+        for &s in &states {
+            marked[s] = false;
+        }
+        states.retain(|&s| self.accept[s] || self.transition[s].iter().any(|x| x.0.is_some()));
+        states.sort_unstable();
+        return states;
+    }
+
+    /// Returns an equivalent DFA by the subset construction.
+    ///
+    /// Only the nonempty sets of states reachable from the start are built,
+    /// so the result may have missing transitions.
+    pub fn determinize(&self) -> DFA<T> {
+        let mut marked = vec![false; self.transition.len()];
+        marked[0] = true;
+        let start = self.closure(vec![0], &mut marked);
+        let mut index: FxHashMap<Vec<usize>, usize> = FxHashMap::default();
+        index.insert(start.clone(), 0);
+        let mut sets = vec![start];
+        let mut transition = Vec::new();
+        let mut accept = Vec::new();
+        let mut next = 0;
+        while next < sets.len() {
+            let mut nt = FxHashMap::default();
+            for l in &self.alphabet {
+                let mut target = Vec::new();
+                for &s1 in &sets[next] {
+                    for &(m, s2) in &self.transition[s1] {
+                        if m == Some(*l) && !marked[s2] {
+                            marked[s2] = true;
+                            target.push(s2);
+                        }
+                    }
+                }
+                let target = self.closure(target, &mut marked);
+                if target.is_empty() {
+                    continue;
+                }
+                let s = match index.get(&target) {
+                    Some(&s) => s,
+                    None => {
+                        index.insert(target.clone(), sets.len());
+                        sets.push(target);
+                        sets.len() - 1
+                    }
+                };
+                nt.insert(*l, s);
+            }
+            transition.push(nt);
+            accept.push(sets[next].iter().any(|&s| self.accept[s]));
+            next += 1;
+        }
+        return DFA {
+            alphabet: self.alphabet.clone(),
+            transition,
+            accept,
+        };
+    }
+}
@@ -0,0 +1,604 @@
+use super::group::Budget;
+use super::transducer::Transducer;
+use num::{BigUint, One};
+use rustc_hash::FxHashMap;
+
+/// A tree automorphism as a product of factors, applied from first to last,
+/// each an index into a table of `Products`.
+type Factors = Vec<usize>;
+
+/// A table of the distinct minimized transducers occurring as factors of
+/// products, whether they are trivial, and their restrictions at each letter
+/// and inverses once computed.
+#[derive(Clone, Debug)]
+struct Products {
+    states: usize,
+    factors: Vec<Transducer>,
+    trivial: Vec<bool>,
+    factor_index: FxHashMap<Transducer, usize>,
+    sections: Vec<Option<Vec<usize>>>,
+    inverses: Vec<Option<usize>>,
+    products: FxHashMap<(usize, usize), usize>,
+}
+
+impl Products {
+    /// Create an empty table, multiplying out factors whose sizes multiply
+    /// to at most `states`.
+    fn new(states: usize) -> Self {
+        Products {
+            states,
+            factors: Vec::new(),
+            trivial: Vec::new(),
+            factor_index: FxHashMap::default(),
+            sections: Vec::new(),
+            inverses: Vec::new(),
+            products: FxHashMap::default(),
+        }
+    }
+
+    /// The index of a minimized transducer in the table of factors.
+    fn intern(&mut self, t: Transducer) -> usize {
+        if let Some(&f) = self.factor_index.get(&t) {
+            return f;
+        }
+        self.factor_index.insert(t.clone(), self.factors.len());
+        self.trivial.push(t.is_identity());
+        self.factors.push(t);
+        self.sections.push(None);
+        self.inverses.push(None);
+        return self.factors.len() - 1;
+    }
+
+    fn section(&mut self, f: usize, x: usize) -> usize {
+        if self.sections[f].is_none() {
+            let sections = self.factors[f]
+                .sections()
+                .into_iter()
+                .map(|t| self.intern(t))
+                .collect();
+            self.sections[f] = Some(sections);
+        }
+        return self.sections[f].as_ref().unwrap()[x];
+    }
+
+    fn inverse(&mut self, f: usize) -> usize {
+        if self.inverses[f].is_none() {
+            let inv = self.factors[f].inverse().minimize();
+            let inv = self.intern(inv);
+            self.inverses[f] = Some(inv);
+            self.inverses[inv] = Some(f);
+        }
+        return self.inverses[f].unwrap();
+    }
+
+    /// The image of the letter `x` under a product.
+    fn image(&self, factors: &[usize], x: usize) -> usize {
+        return factors
+            .iter()
+            .fold(x, |y, &f| self.factors[f].root()[y] as usize);
+    }
+
+    /// Append to `res` the restrictions of the factors of a product at the
+    /// letter `x`, which multiply to the restriction of the product.
+    fn restrictions(&mut self, factors: &[usize], x: usize, res: &mut Factors) {
+        let mut y = x;
+        for &f in factors {
+            res.push(self.section(f, y));
+            y = self.factors[f].root()[y] as usize;
+        }
+    }
+
+    /// The residue `h^m|c` of a product `h` at the letter `c`, where `m` is
+    /// the length of the cycle of its root permutation through `c`.
+    fn residue(&mut self, h: &[usize], c: usize) -> Factors {
+        let mut res = Vec::new();
+        let mut e = c;
+        loop {
+            self.restrictions(h, e, &mut res);
+            e = self.image(h, e);
+            if e == c {
+                break;
+            }
+        }
+        return self.normalize(res);
+    }
+
+    /// Multiply out consecutive factors whose sizes multiply to at most
+    /// `states`, bounding the size of any product built, and drop identity
+    /// factors.
+    fn normalize(&mut self, factors: Factors) -> Factors {
+        let mut res: Factors = Vec::new();
+        for f in factors {
+            if self.trivial[f] {
+                continue;
+            }
+            if let Some(&last) = res.last() {
+                if self.factors[last].len() * self.factors[f].len() <= self.states {
+                    let p = match self.products.get(&(last, f)) {
+                        Some(&p) => p,
+                        None => {
+                            let p = self.factors[last].product(&self.factors[f]);
+                            let p = self.intern(p);
+                            self.products.insert((last, f), p);
+                            p
+                        }
+                    };
+                    res.pop();
+                    if !self.trivial[p] {
+                        res.push(p);
+                    }
+                    continue;
+                }
+            }
+            res.push(f);
+        }
+        return res;
+    }
+}
+
+/// Whether two transducers have the same orbits on every level, together
+/// with its proof.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum OrbitEquivalence {
+    /// The orbits are equal on every level. The minimized transducer sends
+    /// each word to the least word in its orbit under either transducer.
+    Equal(Transducer),
+    /// The orbits differ on the level of the given word, whose orbits under
+    /// the two transducers differ. No shorter word distinguishes them.
+    Distinct(Vec<u8>),
+    /// The budget ran out before the orbits were compared on every level.
+    Unknown,
+}
+
+/// The orbit signature of an invertible transducer `g`: the Mealy machine
+/// sending each word to the least word in its orbit under `g`. Two
+/// transducers have the same orbits on a level exactly when their signatures
+/// agree on it.
+///
+/// Each state is a pair `(t, h)` of automorphisms, and sends `w` to the least
+/// word in the orbit of `t(w)` under `h`; the start state is `(1, g)`. On
+/// reading `x`, let `d = t(x)` lie in a cycle of length `l` of the root
+/// permutation of `h`, with least letter `c` and `d = h^j(c)`. The state
+/// writes `c` and moves to `(t|x h^-j|d, h^l|c)`, since `h^-j` carries the
+/// orbit through `d` below `c`, where `h^l|c` acts on it.
+///
+/// States are built on demand in breadth-first order, so identical
+/// subproblems are only solved once. The automorphisms are kept as products
+/// of distinct minimized transducers, multiplied out while the pairs of
+/// states number at most the states of the budget, so that equal states are
+/// recognized when they are small without the cost of minimizing large
+/// products.
+#[derive(Clone, Debug)]
+pub struct OrbitSignature {
+    alphabet: usize,
+    products: Products,
+    nodes: Vec<(Factors, Factors)>,
+    index: FxHashMap<(Factors, Factors), usize>,
+    /// The letter written and the next state for each expanded state and
+    /// letter read. The states are expanded in order.
+    edges: Vec<Vec<(u8, usize)>>,
+}
+
+impl OrbitSignature {
+    /// Create the signature of an invertible transducer, with only its start
+    /// state built.
+    pub fn new(g: &Transducer, budget: &Budget) -> Self {
+        g.assert_invertible();
+        let mut products = Products::new(budget.states);
+        let f = products.intern(g.minimize());
+        let start = (Vec::new(), products.normalize(vec![f]));
+        let mut index = FxHashMap::default();
+        index.insert(start.clone(), 0);
+        OrbitSignature {
+            alphabet: g.alphabet(),
+            products,
+            nodes: vec![start],
+            index,
+            edges: Vec::new(),
+        }
+    }
+
+    /// The number of states built so far.
+    #[allow(clippy::len_without_is_empty)]
+    pub fn len(&self) -> usize {
+        return self.nodes.len();
+    }
+
+    /// Build the transitions of the next unexpanded state.
+    fn expand(&mut self) {
+        let (t, h) = self.nodes[self.edges.len()].clone();
+        let hinv: Factors = h.iter().rev().map(|&f| self.products.inverse(f)).collect();
+        let mut out = Vec::new();
+        for x in 0..self.alphabet {
+            let d = self.products.image(&t, x);
+            let mut transport = Vec::new();
+            self.products.restrictions(&t, x, &mut transport);
+            let mut c = d;
+            let mut e = self.products.image(&h, d);
+            while e != d {
+                c = c.min(e);
+                e = self.products.image(&h, e);
+            }
+            while e != c {
+                self.products.restrictions(&hinv, e, &mut transport);
+                e = self.products.image(&hinv, e);
+            }
+            let node = (
+                self.products.normalize(transport),
+                self.products.residue(&h, c),
+            );
+            let next = match self.index.get(&node) {
+                Some(&s) => s,
+                None => {
+                    self.index.insert(node.clone(), self.nodes.len());
+                    self.nodes.push(node);
+                    self.nodes.len() - 1
+                }
+            };
+            out.push((c as u8, next));
+        }
+        self.edges.push(out);
+    }
+
+    /// Read the letter `x` from `state`, returning the letter written and the
+    /// next state.
+    pub fn step(&mut self, state: usize, x: u8) -> (u8, usize) {
+        while self.edges.len() <= state {
+            self.expand();
+        }
+        return self.edges[state][x as usize];
+    }
+}
+
+/// An iterator through the cycle types of an invertible transducer on the
+/// levels `n = 0, 1, ...`, each given as the pairs `(length, count)` of its
+/// orbit lengths in increasing order.
+///
+/// An orbit of length `L` on a level, through a word `w`, is tracked only by
+/// `L` and the residue `g^L|w`: each cycle of length `m` of the residue's
+/// root permutation gives an orbit of length `Lm` on the next level. Orbits
+/// with the same length and residue split alike, so they are kept once with
+/// their multiplicity. Lengths and counts grow exponentially with the level,
+/// so they are big integers.
+#[derive(Clone, Debug)]
+pub struct CycleTypes {
+    alphabet: usize,
+    products: Products,
+    orbits: Vec<((BigUint, Factors), BigUint)>,
+}
+
+impl CycleTypes {
+    /// Create an iterator through the cycle types of `g`, multiplying out
+    /// residues as in [`OrbitSignature`].
+    pub fn new(g: &Transducer, budget: &Budget) -> Self {
+        g.assert_invertible();
+        let mut products = Products::new(budget.states);
+        let f = products.intern(g.minimize());
+        let h = products.normalize(vec![f]);
+        CycleTypes {
+            alphabet: g.alphabet(),
+            products,
+            orbits: vec![((BigUint::one(), h), BigUint::one())],
+        }
+    }
+}
+
+impl Iterator for CycleTypes {
+    type Item = Vec<(BigUint, BigUint)>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        let mut counts: FxHashMap<BigUint, BigUint> = FxHashMap::default();
+        for ((len, _), count) in &self.orbits {
+            *counts.entry(len.clone()).or_default() += count;
+        }
+        let mut res: Vec<(BigUint, BigUint)> = counts.into_iter().collect();
+        res.sort_unstable();
+
+        let mut next: FxHashMap<(BigUint, Factors), BigUint> = FxHashMap::default();
+        for ((len, h), count) in std::mem::take(&mut self.orbits) {
+            let mut seen = vec![false; self.alphabet];
+            for a in 0..self.alphabet {
+                let mut m: usize = 0;
+                let mut b = a;
+                while !seen[b] {
+                    seen[b] = true;
+                    m += 1;
+                    b = self.products.image(&h, b);
+                }
+                if m > 0 {
+                    let r = self.products.residue(&h, a);
+                    *next.entry((&len * m, r)).or_default() += &count;
+                }
+            }
+        }
+        self.orbits = next.into_iter().collect();
+        self.orbits.sort_unstable();
+        return Some(res);
+    }
+}
+
+/// Format the cycle types of levels `0, 1, ...` as the truncated series
+/// `Σ c(n, L) x^L z^n`, where `c(n, L)` counts the orbits of length `L` on
+/// level `n`.
+pub fn cycle_series(types: &[Vec<(BigUint, BigUint)>]) -> String {
+    let mut terms = Vec::new();
+    for (n, t) in types.iter().enumerate() {
+        let monomials: Vec<String> = t
+            .iter()
+            .map(|(len, count)| {
+                let c = if count.is_one() {
+                    String::new()
+                } else {
+                    count.to_string()
+                };
+                if len.is_one() {
+                    format!("{}x", c)
+                } else {
+                    format!("{}x^{}", c, len)
+                }
+            })
+            .collect();
+        let p = if monomials.len() == 1 {
+            monomials.join(" + ")
+        } else {
+            format!("({})", monomials.join(" + "))
+        };
+        terms.push(match n {
+            0 => p,
+            1 => format!("{}z", p),
+            _ => format!("{}z^{}", p, n),
+        });
+    }
+    return terms.join(" + ");
+}
+
+/// A node of an [`OrbitTree`], standing for an orbit on the level of its
+/// representative word.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct OrbitNode {
+    /// The least word in the orbit.
+    pub word: Vec<u8>,
+    /// The number of words in the orbit.
+    pub length: usize,
+    /// The states reached after reading `g^i(word)` for `i` in
+    /// `0..length`, that is, the restrictions of `g` along the cycle.
+    pub states: Vec<usize>,
+    /// The indices of the orbits on the next level below this one, in
+    /// increasing order of their words.
+    pub children: Vec<usize>,
+}
+
+/// The orbits of an invertible transducer on the levels up to a depth,
+/// arranged in a tree: the orbits below an orbit on level `n` are those of
+/// its words extended by a letter.
+///
+/// Nodes are numbered in breadth-first order, which sorts their words by
+/// length, then lexicographically. The root is the orbit of the empty word.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct OrbitTree {
+    depth: usize,
+    nodes: Vec<OrbitNode>,
+    index: FxHashMap<Vec<u8>, usize>,
+}
+
+impl OrbitTree {
+    /// Build the orbit tree of `g` down to level `depth`, stepping the
+    /// words of each orbit through `g`.
+    pub fn new(g: &Transducer, depth: usize) -> Self {
+        g.assert_invertible();
+        let mut nodes = vec![OrbitNode {
+            word: Vec::new(),
+            length: 1,
+            states: vec![0],
+            children: Vec::new(),
+        }];
+        let mut level = 0..1;
+        for _ in 0..depth {
+            let start = nodes.len();
+            for parent in level {
+                let n = nodes[parent].word.len();
+                let mut seen = vec![false; g.alphabet()];
+                for c in 0..g.alphabet() as u8 {
+                    if seen[c as usize] {
+                        continue;
+                    }
+                    let mut target = nodes[parent].word.clone();
+                    target.push(c);
+                    let mut v = target.clone();
+                    let mut states = Vec::new();
+                    loop {
+                        states.push(g.step(&mut v));
+                        if v[..n] == target[..n] {
+                            seen[v[n] as usize] = true;
+                        }
+                        if v == target {
+                            break;
+                        }
+                    }
+                    let child = nodes.len();
+                    nodes[parent].children.push(child);
+                    nodes.push(OrbitNode {
+                        word: target,
+                        length: states.len(),
+                        states,
+                        children: Vec::new(),
+                    });
+                }
+            }
+            level = start..nodes.len();
+        }
+        let index = nodes
+            .iter()
+            .enumerate()
+            .map(|(i, node)| (node.word.clone(), i))
+            .collect();
+        OrbitTree {
+            depth,
+            nodes,
+            index,
+        }
+    }
+
+    /// The deepest level of the tree.
+    pub fn depth(&self) -> usize {
+        return self.depth;
+    }
+
+    /// The number of nodes, including the root.
+    #[allow(clippy::len_without_is_empty)]
+    pub fn len(&self) -> usize {
+        return self.nodes.len();
+    }
+
+    /// The root, which is the orbit of the empty word.
+    pub fn root(&self) -> &OrbitNode {
+        return &self.nodes[0];
+    }
+
+    /// The node with index `i`.
+    pub fn node(&self, i: usize) -> &OrbitNode {
+        return &self.nodes[i];
+    }
+
+    /// The node whose representative is `word`, if `word` is the least word
+    /// in its orbit and no longer than the depth.
+    pub fn get(&self, word: &[u8]) -> Option<&OrbitNode> {
+        return self.index.get(word).map(|&i| &self.nodes[i]);
+    }
+
+    /// Iterate through the nodes in breadth-first order.
+    pub fn iter(&self) -> std::slice::Iter<'_, OrbitNode> {
+        return self.nodes.iter();
+    }
+
+    /// Iterate through the orbits directly below `node`.
+    pub fn children<'a>(&'a self, node: &'a OrbitNode) -> impl Iterator<Item = &'a OrbitNode> {
+        return node.children.iter().map(|&i| &self.nodes[i]);
+    }
+
+    /// Iterate through the orbits on level `n`.
+    pub fn level(&self, n: usize) -> impl Iterator<Item = &OrbitNode> {
+        return self.nodes.iter().filter(move |node| node.word.len() == n);
+    }
+
+    /// The tree restricted to the levels up to `depth`.
+    pub fn truncate(&self, depth: usize) -> Self {
+        let nodes: Vec<OrbitNode> = self
+            .nodes
+            .iter()
+            .take_while(|node| node.word.len() <= depth)
+            .cloned()
+            .map(|mut node| {
+                if node.word.len() == depth {
+                    node.children.clear();
+                }
+                node
+            })
+            .collect();
+        let index = nodes
+            .iter()
+            .enumerate()
+            .map(|(i, node)| (node.word.clone(), i))
+            .collect();
+        OrbitTree {
+            depth: depth.min(self.depth),
+            nodes,
+            index,
+        }
+    }
+
+    /// Render the orbits below the root one per line, as the representative
+    /// padded to the depth, followed by the states along the cycle written as
+    /// letters `A, B, C, ...`, or as `[26]`, `[27]`, ... past `Z`.
+    pub fn to_text(&self) -> String {
+        let mut res = String::new();
+        for node in &self.nodes[1..] {
+            let word: String = node.word.iter().map(|x| x.to_string()).collect();
+            let states: String = node
+                .states
+                .iter()
+                .map(|&s| match s {
+                    0..26 => ((b'A' + s as u8) as char).to_string(),
+                    _ => format!("[{}]", s),
+                })
+                .collect();
+            res += &format!("{:depth$} {}\n", word, states, depth = self.depth);
+        }
+        return res;
+    }
+
+    /// Serialize the tree as JSON, with the nodes listed in breadth-first
+    /// order and children referred to by index.
+    pub fn to_json(&self) -> String {
+        fn list<T: ToString>(xs: &[T]) -> String {
+            let xs: Vec<String> = xs.iter().map(|x| x.to_string()).collect();
+            return format!("[{}]", xs.join(","));
+        }
+        let nodes: Vec<String> = self
+            .nodes
+            .iter()
+            .map(|node| {
+                format!(
+                    "{{\"word\":{},\"length\":{},\"states\":{},\"children\":{}}}",
+                    list(&node.word),
+                    node.length,
+                    list(&node.states),
+                    list(&node.children)
+                )
+            })
+            .collect();
+        return format!(
+            "{{\"depth\":{},\"nodes\":[{}]}}",
+            self.depth,
+            nodes.join(",")
+        );
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::transducer::AllTransducers;
+
+    #[test]
+    fn cycle_types_beyond_usize() {
+        let add = Transducer::new(vec![vec![1, 0], vec![1, 1]], vec![1, 0]);
+        let one = BigUint::one();
+        assert_eq!(add.cycle_type(64), vec![(one.clone() << 64, one.clone())]);
+        assert_eq!(add.cycle_type(200), vec![(one.clone() << 200, one.clone())]);
+        let id = Transducer::identity(3);
+        assert_eq!(id.cycle_type(50), vec![(one, BigUint::from(3u32).pow(50))]);
+    }
+
+    #[test]
+    fn cycle_types_match_orbit_tree() {
+        for m in AllTransducers::with_alphabet(3).take(2000).step_by(37) {
+            let tree = OrbitTree::new(&m, 5);
+            for (n, types) in CycleTypes::new(&m, &Budget::default()).take(6).enumerate() {
+                let mut counts: FxHashMap<BigUint, BigUint> = FxHashMap::default();
+                for node in tree.level(n) {
+                    *counts.entry(BigUint::from(node.length)).or_default() += 1u32;
+                }
+                let mut counts: Vec<_> = counts.into_iter().collect();
+                counts.sort_unstable();
+                assert_eq!(types, counts, "{:?}", m);
+            }
+        }
+    }
+
+    #[test]
+    fn orbit_tree_text_past_z() {
+        // The root swaps the letters, moving to state 27 on reading 0 and
+        // to state 199 on reading 1; every other state is the identity.
+        let mut transition: Vec<Vec<usize>> = (0..200).map(|s| vec![s, s]).collect();
+        let mut output = vec![vec![0, 1]; 200];
+        transition[0] = vec![27, 199];
+        output[0] = vec![1, 0];
+        let m = Transducer::with_output(transition, output);
+        assert_eq!(OrbitTree::new(&m, 1).to_text(), "0 [27][199]\n");
+        assert_eq!(
+            OrbitTree::new(&m, 2).to_text(),
+            "0  [27][199]\n00 [27][199]\n01 [27][199]\n"
+        );
+    }
+}
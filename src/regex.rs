@@ -0,0 +1,383 @@
+use super::dfa::DFA;
+use super::nfa::NFA;
+use core::fmt::{Debug, Display};
+use core::hash::Hash;
+
+/// A regular expression over an alphabet of symbols `T`.
+///
+/// The constructors `concat`, `union` and `star` simplify their result with
+/// a few algebraic identities, so they should be preferred over building the
+/// variants directly.
+#[derive(Clone, Debug, PartialEq, Eq, Hash)]
+pub enum Regex<T> {
+    /// The empty language.
+    Empty,
+    /// The language of the empty word.
+    Epsilon,
+    /// The language of a single word of one symbol.
+    Symbol(T),
+    /// The concatenation of two languages.
+    Concat(Box<Regex<T>>, Box<Regex<T>>),
+    /// The union of two languages.
+    Union(Box<Regex<T>>, Box<Regex<T>>),
+    /// The Kleene star of a language.
+    Star(Box<Regex<T>>),
+}
+
+impl<T: Copy + Eq + Hash + Debug> Regex<T> {
+    /// The number of nodes of the expression.
+    fn size(&self) -> usize {
+        return match self {
+            Regex::Empty | Regex::Epsilon | Regex::Symbol(_) => 1,
+            Regex::Concat(x, y) | Regex::Union(x, y) => 1 + x.size() + y.size(),
+            Regex::Star(x) => 1 + x.size(),
+        };
+    }
+
+    /// Returns the concatenation of two regular expressions.
+    pub fn concat(self, other: Self) -> Self {
+        return match (self, other) {
+            (Regex::Empty, _) | (_, Regex::Empty) => Regex::Empty,
+            (Regex::Epsilon, x) | (x, Regex::Epsilon) => x,
+            (x, y) => Regex::Concat(Box::new(x), Box::new(y)),
+        };
+    }
+
+    /// Returns the union of two regular expressions.
+    pub fn union(self, other: Self) -> Self {
+        return match (self, other) {
+            (Regex::Empty, x) | (x, Regex::Empty) => x,
+            (Regex::Epsilon, Regex::Star(x)) | (Regex::Star(x), Regex::Epsilon) => Regex::Star(x),
+            (x, y) if x == y => x,
+            (x, y) => Regex::Union(Box::new(x), Box::new(y)),
+        };
+    }
+
+    /// Returns the Kleene star of a regular expression.
+    pub fn star(self) -> Self {
+        return match self {
+            Regex::Empty | Regex::Epsilon => Regex::Epsilon,
+            Regex::Star(x) => Regex::Star(x),
+            x => Regex::Star(Box::new(x)),
+        };
+    }
+
+    /// Parse a regular expression.
+    ///
+    /// The syntax has `|` for union, juxtaposition for concatenation, postfix
+    /// `*`, `+` and `?`, and parentheses for grouping. `ε` and `∅` stand for
+    /// the empty word and the empty language, and whitespace is ignored. Any
+    /// other character, or a nonempty name written as `<name>`, is a symbol,
+    /// which is looked up with `symbol`. Positions in errors do not count
+    /// whitespace.
+    pub fn parse(s: &str, symbol: impl Fn(&str) -> Option<T>) -> Result<Self, String> {
+        let mut parser = Parser {
+            chars: s.chars().filter(|c| !c.is_whitespace()).collect(),
+            position: 0,
+            symbol,
+        };
+        let res = parser.union()?;
+        if parser.position < parser.chars.len() {
+            return Err(format!(
+                "unexpected {:?} at position {}.",
+                parser.chars[parser.position], parser.position
+            ));
+        }
+        return Ok(res);
+    }
+
+    /// Returns an NFA accepting the language of the expression, by
+    /// Thompson's construction.
+    pub fn to_nfa(&self, alphabet: &[T]) -> NFA<T> {
+        return match self {
+            Regex::Empty => NFA::empty(alphabet.to_vec()),
+            Regex::Epsilon => NFA::epsilon(alphabet.to_vec()),
+            Regex::Symbol(l) => NFA::symbol(alphabet.to_vec(), *l),
+            Regex::Concat(x, y) => x.to_nfa(alphabet).concat(&y.to_nfa(alphabet)),
+            Regex::Union(x, y) => x.to_nfa(alphabet).union(&y.to_nfa(alphabet)),
+            Regex::Star(x) => x.to_nfa(alphabet).star(),
+        };
+    }
+
+    /// Returns the canonical DFA accepting the language of the expression.
+    pub fn to_dfa(&self, alphabet: &[T]) -> DFA<T> {
+        return self.to_nfa(alphabet).determinize().canonicalize();
+    }
+
+    /// Returns a regular expression for the language of a DFA, by state
+    /// elimination on its canonical form.
+    ///
+    /// States are eliminated greedily, each time choosing one with the
+    /// fewest pairs of incoming and outgoing edges, since each such pair
+    /// adds a path to the expression. Ties go to the state whose paths copy
+    /// the fewest nodes of the expressions on its edges.
+    pub fn from_dfa(dfa: &DFA<T>) -> Self {
+        let dfa = dfa.canonicalize();
+        let n = dfa.transition.len();
+        // States `0..n` are those of the DFA, `n` is a new start state and
+        // `n + 1` a new final state.
+        let mut r = vec![vec![Regex::Empty; n + 2]; n + 2];
+        r[n][0] = Regex::Epsilon;
+        for (s1, nt) in dfa.transition.iter().enumerate() {
+            for &l in &dfa.alphabet {
+                if let Some(&s2) = nt.get(&l) {
+                    r[s1][s2] = r[s1][s2].clone().union(Regex::Symbol(l));
+                }
+            }
+            if dfa.accept[s1] {
+                r[s1][n + 1] = Regex::Epsilon;
+            }
+        }
+        let mut remaining: Vec<usize> = (0..n).collect();
+        while !remaining.is_empty() {
+            let edges = |q: usize| {
+                let sources: Vec<usize> = (0..n + 2)
+                    .filter(|&i| i != q && r[i][q] != Regex::Empty)
+                    .collect();
+                let targets: Vec<usize> = (0..n + 2)
+                    .filter(|&j| j != q && r[q][j] != Regex::Empty)
+                    .collect();
+                (sources, targets)
+            };
+            let (i, &q) = remaining
+                .iter()
+                .enumerate()
+                .min_by_key(|&(_, &q)| {
+                    let (sources, targets) = edges(q);
+                    let weight = sources.iter().map(|&i| r[i][q].size()).sum::<usize>()
+                        * targets.len()
+                        + targets.iter().map(|&j| r[q][j].size()).sum::<usize>() * sources.len()
+                        + r[q][q].size() * sources.len() * targets.len();
+                    (sources.len() * targets.len(), weight)
+                })
+                .unwrap();
+            remaining.remove(i);
+            let (sources, targets) = edges(q);
+            let loop_q = r[q][q].clone().star();
+            for &i in &sources {
+                for &j in &targets {
+                    let path = r[i][q]
+                        .clone()
+                        .concat(loop_q.clone())
+                        .concat(r[q][j].clone());
+                    r[i][j] = r[i][j].clone().union(path);
+                }
+            }
+            for row in r.iter_mut() {
+                row[q] = Regex::Empty;
+            }
+            r[q] = vec![Regex::Empty; n + 2];
+        }
+        return r[n][n + 1].clone();
+    }
+}
+
+impl<T: Display> Regex<T> {
+    /// Write the expression, with parentheses around it if its precedence is
+    /// below `precedence`: 0 for unions, 1 for concatenations, 2 for stars.
+    fn fmt_precedence(&self, f: &mut core::fmt::Formatter, precedence: u8) -> core::fmt::Result {
+        let (own, res) = match self {
+            Regex::Empty => return write!(f, "∅"),
+            Regex::Epsilon => return write!(f, "ε"),
+            Regex::Symbol(l) => {
+                let name = l.to_string();
+                let mut chars = name.chars();
+                if let (Some(c), None) = (chars.next(), chars.next()) {
+                    if !"|*+?()<>ε∅".contains(c) {
+                        return write!(f, "{}", name);
+                    }
+                }
+                return write!(f, "<{}>", name);
+            }
+            Regex::Union(x, y) => (0, (x, Some(y))),
+            Regex::Concat(x, y) => (1, (x, Some(y))),
+            Regex::Star(x) => (2, (x, None)),
+        };
+        if own < precedence {
+            write!(f, "(")?;
+        }
+        match res {
+            (x, Some(y)) => {
+                x.fmt_precedence(f, own)?;
+                if own == 0 {
+                    write!(f, "|")?;
+                }
+                y.fmt_precedence(f, own)?;
+            }
+            (x, None) => {
+                x.fmt_precedence(f, 3)?;
+                write!(f, "*")?;
+            }
+        }
+        if own < precedence {
+            write!(f, ")")?;
+        }
+        return Ok(());
+    }
+}
+
+impl<T: Display> Display for Regex<T> {
+    fn fmt(&self, f: &mut core::fmt::Formatter) -> core::fmt::Result {
+        return self.fmt_precedence(f, 0);
+    }
+}
+
+/// A recursive descent parser for regular expressions.
+struct Parser<F> {
+    chars: Vec<char>,
+    position: usize,
+    symbol: F,
+}
+
+impl<T: Copy + Eq + Hash + Debug, F: Fn(&str) -> Option<T>> Parser<F> {
+    fn peek(&self) -> Option<char> {
+        return self.chars.get(self.position).copied();
+    }
+
+    fn union(&mut self) -> Result<Regex<T>, String> {
+        let mut res = self.concat()?;
+        while self.peek() == Some('|') {
+            self.position += 1;
+            res = res.union(self.concat()?);
+        }
+        return Ok(res);
+    }
+
+    fn concat(&mut self) -> Result<Regex<T>, String> {
+        let mut res = Regex::Epsilon;
+        while let Some(c) = self.peek() {
+            if c == '|' || c == ')' {
+                break;
+            }
+            res = res.concat(self.postfix()?);
+        }
+        return Ok(res);
+    }
+
+    fn postfix(&mut self) -> Result<Regex<T>, String> {
+        let mut res = self.atom()?;
+        while let Some(c) = self.peek() {
+            res = match c {
+                '*' => res.star(),
+                '+' => res.clone().concat(res.star()),
+                '?' => Regex::Epsilon.union(res),
+                _ => break,
+            };
+            self.position += 1;
+        }
+        return Ok(res);
+    }
+
+    fn atom(&mut self) -> Result<Regex<T>, String> {
+        let start = self.position;
+        let c = self.peek().ok_or("unexpected end of expression.")?;
+        self.position += 1;
+        let name: String = match c {
+            '(' => {
+                let res = self.union()?;
+                if self.peek() != Some(')') {
+                    return Err(format!("unclosed parenthesis at position {}.", start));
+                }
+                self.position += 1;
+                return Ok(res);
+            }
+            'ε' => return Ok(Regex::Epsilon),
+            '∅' => return Ok(Regex::Empty),
+            '<' => {
+                // Names are nonempty, so `<>>` is the symbol `>`.
+                let end = (self.position + 1..self.chars.len())
+                    .find(|&i| self.chars[i] == '>')
+                    .ok_or(format!("unclosed name at position {}.", start))?;
+                let name = self.chars[self.position..end].iter().collect();
+                self.position = end + 1;
+                name
+            }
+            ')' | '|' | '*' | '+' | '?' | '>' => {
+                return Err(format!("unexpected {:?} at position {}.", c, start));
+            }
+            c => c.to_string(),
+        };
+        return match (self.symbol)(&name) {
+            Some(l) => Ok(Regex::Symbol(l)),
+            None => Err(format!("unknown symbol {:?} at position {}.", name, start)),
+        };
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn bit(s: &str) -> Option<u8> {
+        return match s {
+            "0" => Some(0),
+            "1" => Some(1),
+            _ => None,
+        };
+    }
+
+    fn char(s: &str) -> Option<char> {
+        let mut chars = s.chars();
+        return match (chars.next(), chars.next()) {
+            (Some(c), None) => Some(c),
+            _ => None,
+        };
+    }
+
+    #[test]
+    fn round_trip() {
+        for s in [
+            "(0|1)*0(0|1)(0|1)",
+            "0*1*",
+            "(01)+|1?",
+            "((0|1)(0|1))*",
+            "(0*10*1)*0*",
+            "ε",
+            "∅",
+            "<0><1>",
+        ] {
+            let dfa = Regex::parse(s, bit).unwrap().to_dfa(&[0, 1]);
+            let back = Regex::from_dfa(&dfa);
+            assert!(back.to_dfa(&[0, 1]).is_equivalent(&dfa), "{} {}", s, back);
+            let printed = Regex::parse(&back.to_string(), bit).unwrap();
+            assert!(
+                printed.to_dfa(&[0, 1]).is_equivalent(&dfa),
+                "{} {}",
+                s,
+                back
+            );
+        }
+        for (s, len) in [("(0|1)*0(0|1)(0|1)", 400), ("(0|1)*0(0|1)(0|1)(0|1)", 4000)] {
+            let dfa = Regex::parse(s, bit).unwrap().to_dfa(&[0, 1]);
+            assert!(Regex::from_dfa(&dfa).to_string().len() < len, "{}", s);
+        }
+    }
+
+    #[test]
+    fn special_symbols() {
+        let alphabet: Vec<char> = "|*+?()<>ε∅a".chars().collect();
+        let mut r = Regex::Epsilon;
+        for &c in &alphabet {
+            r = r.concat(Regex::Symbol(c).union(Regex::Symbol('a')).star());
+        }
+        let printed = Regex::parse(&r.to_string(), char).unwrap();
+        assert!(printed
+            .to_dfa(&alphabet)
+            .is_equivalent(&r.to_dfa(&alphabet)));
+    }
+
+    #[test]
+    fn errors() {
+        for (s, e) in [
+            ("0|(1", "unclosed parenthesis at position 2."),
+            ("0)", "unexpected ')' at position 1."),
+            ("0 | *", "unexpected '*' at position 2."),
+            ("02", "unknown symbol \"2\" at position 1."),
+            ("1<0", "unclosed name at position 1."),
+            ("<>", "unclosed name at position 0."),
+            ("(", "unclosed parenthesis at position 0."),
+        ] {
+            assert_eq!(Regex::parse(s, bit), Err(e.to_string()), "{}", s);
+        }
+    }
+}
@@ -4,6 +4,10 @@ use super::combi::TCycle;
 use super::combi::UCycle;
 use super::combi::VCycle;
 use super::dfa::DFA;
+use super::group::Budget;
+use super::group::Order;
+use super::orbit::{cycle_series, CycleTypes, OrbitEquivalence, OrbitSignature, OrbitTree};
+use super::word::StepTable;
 use core::hash::Hash;
 use graphviz_rust::cmd::CommandArg;
 use graphviz_rust::cmd::Format;
@@ -11,18 +15,25 @@ use graphviz_rust::dot_generator::*;
 use graphviz_rust::dot_structures::*;
 use graphviz_rust::exec;
 use graphviz_rust::printer::PrinterContext;
+use num::{BigUint, Integer};
 use rayon::prelude::*;
 use rustc_hash::FxHashMap;
 use rustc_hash::FxHashSet;
 
-/// A transducer. By convention, state `0` is the start state.
+/// A transducer, or Mealy machine, over the alphabet `0..k`. By convention,
+/// state `0` is the start state.
+///
+/// Each state reads a letter, writes its image under the state's output map,
+/// and moves to the next state. The binary case, where every state either
+/// fixes or swaps `0` and `1`, is the most common.
 ///
 /// Warning: the representation only assumes the transducer is deterministic
-/// and alphabetic, but most methods also assume it's reversible.
+/// and alphabetic. Methods that follow orbits also need it to be invertible,
+/// and check this at runtime.
 #[derive(Clone, Debug, PartialEq, Eq, PartialOrd, Hash, Ord)]
 pub struct Transducer {
     transition: Vec<Vec<usize>>,
-    flip: Vec<u8>,
+    output: Vec<Vec<u8>>,
 }
 
 fn repr(word: &[u8]) -> String {
@@ -32,28 +43,163 @@ fn repr(word: &[u8]) -> String {
         .join("")
 }
 
+fn is_permutation(map: &[u8]) -> bool {
+    let mut seen = vec![false; map.len()];
+    for &b in map {
+        if b as usize >= map.len() || seen[b as usize] {
+            return false;
+        }
+        seen[b as usize] = true;
+    }
+    return true;
+}
+
+/// The least word in the orbit of a word of `len` bits packed into a limb,
+/// under an invertible binary transducer with the given step table.
+fn min_limb(table: &StepTable, word: u64, len: usize) -> u64 {
+    let mut min = word;
+    let mut next = table.step_limb(0, word, len).0;
+    while next != word {
+        min = min.min(next);
+        next = table.step_limb(0, next, len).0;
+    }
+    return min;
+}
+
 impl Transducer {
-    /// Create a new transducer.
+    /// Create a new transducer on the binary alphabet, where state `s` swaps
+    /// `0` and `1` exactly when `flip[s]` is nonzero.
     pub fn new(transition: Vec<Vec<usize>>, flip: Vec<u8>) -> Self {
-        Transducer { transition, flip }
+        let output = flip
+            .iter()
+            .map(|&f| if f != 0 { vec![1, 0] } else { vec![0, 1] })
+            .collect();
+        return Transducer::with_output(transition, output);
+    }
+
+    /// Create a new transducer where state `s` writes `output[s][a]` on
+    /// reading `a`.
+    ///
+    /// There must be at least one state, and as many outputs as states. Every
+    /// `transition[s]` must have the same length `k`, with `1 <= k <= 255` so
+    /// that letters fit in a byte, and lead to states. Every `output[s]` must
+    /// map the alphabet `0..k` into itself.
+    pub fn with_output(transition: Vec<Vec<usize>>, output: Vec<Vec<u8>>) -> Self {
+        if transition.is_empty() {
+            panic!("transducer has no states.");
+        }
+        if transition.len() != output.len() {
+            panic!(
+                "transducer has {} states but {} outputs.",
+                transition.len(),
+                output.len()
+            );
+        }
+        let k = transition[0].len();
+        if k == 0 || k > 255 {
+            panic!("alphabet size {} is not between 1 and 255.", k);
+        }
+        let n = transition.len();
+        for (s, (t, o)) in transition.iter().zip(output.iter()).enumerate() {
+            if t.len() != k || t.iter().any(|&r| r >= n) {
+                panic!("state {} has invalid transition {:?}.", s, t);
+            }
+            if o.len() != t.len() || o.iter().any(|&b| b as usize >= t.len()) {
+                panic!("state {} has invalid output {:?}.", s, o);
+            }
+        }
+        Transducer { transition, output }
     }
 
-    /// TODO
+    /// Create the one-state transducer on the alphabet `0..k` that fixes
+    /// every word.
+    pub fn identity(k: usize) -> Self {
+        return Transducer::with_output(vec![vec![0; k]], vec![(0..k).map(|a| a as u8).collect()]);
+    }
+
+    /// The number of states.
+    #[allow(clippy::len_without_is_empty)]
     pub fn len(&self) -> usize {
         return self.transition.len();
     }
 
-    /// TODO
-    pub fn step(&self, x: &mut Vec<u8>) -> usize {
+    /// The size `k` of the alphabet `0..k`.
+    pub fn alphabet(&self) -> usize {
+        return self.transition[0].len();
+    }
+
+    /// Whether every state's output map is a permutation, so that the
+    /// transducer defines an automorphism of the tree of words.
+    pub fn is_invertible(&self) -> bool {
+        return self.output.iter().all(|o| is_permutation(o));
+    }
+
+    /// Panic unless the transducer is invertible.
+    pub(crate) fn assert_invertible(&self) {
+        if !self.is_invertible() {
+            panic!("transducer {:?} is not invertible.", self);
+        }
+    }
+
+    fn active(&self, state: usize) -> bool {
+        return self.output[state]
+            .iter()
+            .enumerate()
+            .any(|(a, &b)| a != b as usize);
+    }
+
+    /// Find a shortest word changed by the transducer, if there is one.
+    pub fn witness(&self) -> Option<Vec<u8>> {
+        let mut parent: Vec<Option<(usize, u8)>> = vec![None; self.transition.len()];
+        let mut seen = vec![false; self.transition.len()];
+        let mut frontier = vec![0];
+        seen[0] = true;
+        while !frontier.is_empty() {
+            let mut new_frontier = Vec::new();
+            for &s1 in &frontier {
+                if self.active(s1) {
+                    let mut word = vec![(0..self.alphabet())
+                        .find(|&a| self.output[s1][a] as usize != a)
+                        .unwrap() as u8];
+                    let mut s = s1;
+                    while let Some((prev, a)) = parent[s] {
+                        word.push(a);
+                        s = prev;
+                    }
+                    word.reverse();
+                    return Some(word);
+                }
+                for (a, &s2) in self.transition[s1].iter().enumerate() {
+                    if !seen[s2] {
+                        seen[s2] = true;
+                        parent[s2] = Some((s1, a as u8));
+                        new_frontier.push(s2);
+                    }
+                }
+            }
+            frontier = new_frontier;
+        }
+        return None;
+    }
+
+    /// Whether the transducer fixes every word.
+    pub fn is_identity(&self) -> bool {
+        return self.witness().is_none();
+    }
+
+    /// Apply the transducer to `x` in place, returning the final state.
+    pub fn step(&self, x: &mut [u8]) -> usize {
         let mut state: usize = 0;
-        for i in 0..x.len() {
-            let c = x[i];
-            x[i] = c ^ self.flip[state];
-            state = self.transition[state][c as usize];
+        for c in x.iter_mut() {
+            let a = *c as usize;
+            *c = self.output[state][a];
+            state = self.transition[state][a];
         }
         return state;
     }
 
+    /// The least word in the orbit of `word`. The transducer must be
+    /// invertible, which is left to the caller to check once.
     fn min_word(&self, word: &[u8]) -> Vec<u8> {
         let mut min = word.to_vec();
         let mut next = word.to_vec();
@@ -69,34 +215,167 @@ impl Transducer {
         }
     }
 
+    /// Build the tables stepping a binary transducer through packed words.
+    pub fn step_table(&self) -> StepTable {
+        assert_eq!(self.alphabet(), 2);
+        return StepTable::new(self.len(), |s, b| {
+            (self.output[s][b as usize], self.transition[s][b as usize])
+        });
+    }
+
     /// Compare the orbits of two transducers on strings up to length `depth`.
     ///
-    /// At least O(2^n) time where n is `depth`, but memory-efficient.
+    /// At least O(k^n) time where n is `depth`, but memory-efficient. Binary
+    /// transducers step through packed words a byte at a time.
     pub fn orbit_compare(&self, other: &Transducer, depth: usize) -> bool {
-        let xs = VCycle::<u8, UCycle<u8>>::new(vec![2; depth], true);
+        assert_eq!(self.alphabet(), other.alphabet());
+        self.assert_invertible();
+        other.assert_invertible();
+        if self.alphabet() == 2 && 0 < depth && depth < 64 {
+            let (a, b) = (self.step_table(), other.step_table());
+            return (0..1u64 << depth).into_par_iter().all(|x| {
+                let word = x << (64 - depth);
+                min_limb(&a, word, depth) == min_limb(&b, word, depth)
+            });
+        }
+        let xs = VCycle::<u8, UCycle<u8>>::new(vec![self.alphabet() as u8; depth], true);
         return xs
             .par_bridge()
             .all(|word| self.min_word(&word) == other.min_word(&word));
     }
 
-    /// Minimize a given transducer.
+    /// Compare the orbits of two transducers on strings up to length `depth`,
+    /// giving the same answer as [`Transducer::orbit_compare`].
     ///
-    /// This uses the DFA representation of a transducer. DFAs minimization is
-    /// well-known and the injection from transducers to DFAs preserves
-    /// equivalence.
-    pub fn minimize(&self) -> Self {
-        let alphabet = TCycle::<u8, u8, UCycle<u8>, UCycle<u8>>::new((2, 2), true).collect();
-        let mut accept = vec![false; self.transition.len()];
-        accept.push(true);
+    /// Searches the pairs of states of the two orbit signatures reachable in
+    /// fewer than `depth` steps for one where they write different letters,
+    /// so it is polynomial in the number of such pairs rather than
+    /// exponential in `depth`.
+    pub fn orbit_equal(&self, other: &Transducer, depth: usize) -> bool {
+        assert_eq!(self.alphabet(), other.alphabet());
+        let mut a = OrbitSignature::new(self, &Budget::default());
+        let mut b = OrbitSignature::new(other, &Budget::default());
+        let mut seen = FxHashSet::default();
+        let mut frontier = vec![(0, 0)];
+        seen.insert((0, 0));
+        for _ in 0..depth {
+            let mut new_frontier = Vec::new();
+            for &(p, q) in &frontier {
+                for x in 0..self.alphabet() as u8 {
+                    let (c1, p2) = a.step(p, x);
+                    let (c2, q2) = b.step(q, x);
+                    if c1 != c2 {
+                        return false;
+                    }
+                    if seen.insert((p2, q2)) {
+                        new_frontier.push((p2, q2));
+                    }
+                }
+            }
+            frontier = new_frontier;
+        }
+        return true;
+    }
+
+    /// Decide whether two transducers have the same orbits on every level.
+    ///
+    /// The pairs of states of the two orbit signatures reachable from the
+    /// start form a transducer, which is finite when both signatures are.
+    /// This searches it breadth-first for a pair where the signatures write
+    /// different letters, and returns the word leading there. If there is no
+    /// such pair, the transducer of pairs computes the common signature.
+    /// Gives up once more pairs than `budget.elements` are found.
+    pub fn orbit_equivalence(&self, other: &Transducer, budget: &Budget) -> OrbitEquivalence {
+        assert_eq!(self.alphabet(), other.alphabet());
+        let mut a = OrbitSignature::new(self, budget);
+        let mut b = OrbitSignature::new(other, budget);
+        let mut index = FxHashMap::default();
+        let mut pairs = vec![(0, 0)];
+        let mut parent: Vec<Option<(usize, u8)>> = vec![None];
+        let mut transition = Vec::new();
+        let mut output = Vec::new();
+        index.insert((0, 0), 0);
+        while transition.len() < pairs.len() {
+            let i = transition.len();
+            let (p, q) = pairs[i];
+            let mut nt = Vec::new();
+            let mut out = Vec::new();
+            for x in 0..self.alphabet() as u8 {
+                let (c1, p2) = a.step(p, x);
+                let (c2, q2) = b.step(q, x);
+                if c1 != c2 {
+                    let mut word = vec![x];
+                    let mut s = i;
+                    while let Some((prev, y)) = parent[s] {
+                        word.push(y);
+                        s = prev;
+                    }
+                    word.reverse();
+                    return OrbitEquivalence::Distinct(word);
+                }
+                let next = match index.get(&(p2, q2)) {
+                    Some(&s) => s,
+                    None => {
+                        if pairs.len() >= budget.elements {
+                            return OrbitEquivalence::Unknown;
+                        }
+                        index.insert((p2, q2), pairs.len());
+                        pairs.push((p2, q2));
+                        parent.push(Some((i, x)));
+                        pairs.len() - 1
+                    }
+                };
+                nt.push(next);
+                out.push(c1);
+            }
+            transition.push(nt);
+            output.push(out);
+        }
+        return OrbitEquivalence::Equal(Transducer::with_output(transition, output).minimize());
+    }
+
+    /// The cycle type of the transducer on level `level`, as the pairs
+    /// `(length, count)` of its orbit lengths in increasing order.
+    ///
+    /// Computed level by level from the orbits of the previous level, as in
+    /// [`CycleTypes`].
+    pub fn cycle_type(&self, level: usize) -> Vec<(BigUint, BigUint)> {
+        return CycleTypes::new(self, &Budget::default())
+            .nth(level)
+            .unwrap();
+    }
+
+    /// The cycle types of levels `0..levels` as a truncated generating
+    /// function, formatted by [`cycle_series`].
+    pub fn cycle_series(&self, levels: usize) -> String {
+        let types: Vec<_> = CycleTypes::new(self, &Budget::default())
+            .take(levels)
+            .collect();
+        return cycle_series(&types);
+    }
+
+    /// Encode the transducer as a DFA over pairs `(a, b)` of letters, which
+    /// accepts a pair of words exactly when the transducer writes the second
+    /// on reading the first.
+    ///
+    /// The injection from transducers to DFAs preserves equivalence.
+    pub fn to_dfa(&self) -> DFA<(u8, u8)> {
+        let k = self.alphabet() as u8;
+        let alphabet = TCycle::<u8, u8, UCycle<u8>, UCycle<u8>>::new((k, k), true).collect();
+        let mut accept = vec![true; self.transition.len()];
+        accept.push(false);
         let mut transition = Vec::new();
         for s1 in 0..self.transition.len() {
             let mut nt = FxHashMap::default();
-            for a in 0..2 {
+            for a in 0..k {
                 let s2 = self.transition[s1][a as usize];
-                let b = (a + self.flip[s1]) % 2;
-                let b0 = (a + self.flip[s1] + 1) % 2;
-                nt.insert((a, b), s2);
-                nt.insert((a, b0), self.transition.len());
+                for b in 0..k {
+                    if b == self.output[s1][a as usize] {
+                        nt.insert((a, b), s2);
+                    } else {
+                        nt.insert((a, b), self.transition.len());
+                    }
+                }
             }
             transition.push(nt);
         }
@@ -105,46 +384,67 @@ impl Transducer {
             nt.insert(c, self.transition.len());
         }
         transition.push(nt);
-        let dfa = DFA {
+        return DFA {
             alphabet,
             transition,
             accept,
-        }
-        .minimize();
-        let mut sink = 0;
-        for (s, &v) in dfa.accept.iter().enumerate() {
-            if v {
-                sink = s;
+        };
+    }
+
+    /// Minimize a given transducer.
+    ///
+    /// This refines the partition of states by output until the successors of
+    /// equivalent states are equivalent, as in Moore's algorithm for DFAs.
+    ///
+    /// The states of the result are numbered in breadth-first order, so two
+    /// transducers are equivalent exactly when their minimizations are equal.
+    pub fn minimize(&self) -> Self {
+        return minimize_flat(
+            self.alphabet(),
+            &self.transition.concat(),
+            &self.output.concat(),
+        );
+    }
+
+    /// Renumber the states in breadth-first order from `start`, reading
+    /// letters in increasing order, so that `start` becomes the start state.
+    /// Unreachable states keep their relative order at the end.
+    fn renumber(&self, start: usize) -> Self {
+        let mut order = vec![start];
+        let mut map = vec![usize::MAX; self.transition.len()];
+        map[start] = 0;
+        let mut i = 0;
+        while i < order.len() {
+            for &s2 in &self.transition[order[i]] {
+                if map[s2] == usize::MAX {
+                    map[s2] = order.len();
+                    order.push(s2);
+                }
             }
+            i += 1;
         }
-        let mut new_transition = Vec::new();
-        let mut new_flip = Vec::new();
-        for s1 in 0..dfa.transition.len() {
-            let mut tn = vec![0; 2];
-            let mut flip = false;
-            if s1 == sink {
-                continue;
+        for (s, m) in map.iter_mut().enumerate() {
+            if *m == usize::MAX {
+                *m = order.len();
+                order.push(s);
             }
-            for &(a, b) in &dfa.alphabet {
-                if dfa.transition[s1][&(a, b)] != sink {
-                    flip = a != b;
-                    let s2 = dfa.transition[s1][&(a, b)];
-                    if s2 < sink {
-                        tn[a as usize] = s2;
-                    } else {
-                        tn[a as usize] = s2 - 1;
-                    }
-                }
-            }
-            new_flip.push(flip as u8);
-            new_transition.push(tn);
         }
         return Transducer {
-            transition: new_transition,
-            flip: new_flip,
+            transition: order
+                .iter()
+                .map(|&s| self.transition[s].iter().map(|&t| map[t]).collect())
+                .collect(),
+            output: order.iter().map(|&s| self.output[s].clone()).collect(),
         };
     }
 
+    /// The automorphism defined by each state, as a minimized transducer.
+    pub fn generators(&self) -> Vec<Self> {
+        return (0..self.transition.len())
+            .map(|s| self.renumber(s).minimize())
+            .collect();
+    }
+
     /// Given a permutation of its states, create the corresponding
     ///  graph-isomorphic transducer.
     ///
@@ -159,7 +459,7 @@ impl Transducer {
             for j in i..map.len() {
                 if map[&i] == map[&j] {
                     for (a, b) in self.transition[i].iter().zip(self.transition[j].iter()) {
-                        if map[&a] != map[&b] {
+                        if map[a] != map[b] {
                             panic!("map {:?} is not a valid endomorphism.", map);
                         }
                     }
@@ -168,247 +468,618 @@ impl Transducer {
         }
 
         let mut new_transition: Vec<Vec<usize>> = vec![Vec::new(); self.transition.len()];
-        let mut new_flip = vec![0; self.transition.len()];
+        let mut new_output = vec![Vec::new(); self.transition.len()];
         for state in 0..self.transition.len() {
-            for sym in 0..2 {
+            for sym in 0..self.alphabet() {
                 new_transition[map[&state]].push(map[&self.transition[state][sym]]);
             }
-            new_flip[map[&state]] = self.flip[state];
+            new_output[map[&state]] = self.output[state].clone();
         }
         return Transducer {
             transition: new_transition,
-            flip: new_flip,
+            output: new_output,
         };
     }
 
     /// Produce the inverse transducer, that is, one that undoes the operation
     /// of the given transducer.
     pub fn inverse(&self) -> Self {
+        self.assert_invertible();
         let mut new_transition: Vec<Vec<usize>> = Vec::new();
+        let mut new_output: Vec<Vec<u8>> = Vec::new();
         for state in 0..self.transition.len() {
-            if self.flip[state] != 0 {
-                new_transition.push(self.transition[state].clone().into_iter().rev().collect());
-            } else {
-                new_transition.push(self.transition[state].clone());
+            let mut nt = vec![0; self.alphabet()];
+            let mut out = vec![0; self.alphabet()];
+            for a in 0..self.alphabet() {
+                let b = self.output[state][a] as usize;
+                nt[b] = self.transition[state][a];
+                out[b] = a as u8;
             }
+            new_transition.push(nt);
+            new_output.push(out);
         }
         return Transducer {
             transition: new_transition,
-            flip: self.flip.clone(),
+            output: new_output,
         };
     }
 
+    /// Produce the dual transducer, whose states are the letters of the
+    /// alphabet and whose alphabet is the set of states.
+    ///
+    /// On reading state `s`, dual state `a` writes `transition[s][a]` and
+    /// moves to `output[s][a]`. The dual is usually not invertible, even when
+    /// the transducer is.
+    pub fn dual(&self) -> Self {
+        if self.transition.len() > 256 {
+            panic!("transducer has too many states to be an alphabet.");
+        }
+        let mut new_transition = Vec::new();
+        let mut new_output = Vec::new();
+        for a in 0..self.alphabet() {
+            let mut nt = Vec::new();
+            let mut out = Vec::new();
+            for s in 0..self.transition.len() {
+                nt.push(self.output[s][a] as usize);
+                out.push(self.transition[s][a] as u8);
+            }
+            new_transition.push(nt);
+            new_output.push(out);
+        }
+        return Transducer {
+            transition: new_transition,
+            output: new_output,
+        };
+    }
+
+    /// Whether the transducer, its dual, and the dual of its inverse are all
+    /// invertible.
+    pub fn is_bireversible(&self) -> bool {
+        return self.is_invertible()
+            && self.dual().is_invertible()
+            && self.inverse().dual().is_invertible();
+    }
+
     /// Create a canonical representation of a transducer under graph
-    /// isomorphism and inversion.
+    /// isomorphism and, if it is invertible, inversion.
     ///
-    /// Exponential in the number of states.
+    /// Numbers the states in breadth-first order from the start state, which
+    /// is canonical for transducers whose states are all reachable. Linear in
+    /// the size of the transducer.
     pub fn canonicalize(&self) -> Self {
-        let mut min = self.clone();
-        for m in Permutation::new(self.transition.len() - 1) {
-            let mut map = FxHashMap::default();
-            map.insert(0, 0);
-            for (i, j) in m.iter().enumerate() {
-                map.insert(i + 1, j + 1);
-            }
-            let cand1 = self.relabel(map);
-            let cand2 = cand1.inverse();
-            if cand1 < min {
-                min = cand1;
-            }
-            if cand2 < min {
-                min = cand2;
+        let min = self.renumber(0);
+        if self.is_invertible() {
+            let inv = self.inverse().renumber(0);
+            if inv < min {
+                return inv;
             }
         }
         return min;
     }
 
     /// Create the minimized semigroup product of two transducers.
+    ///
+    /// The product applies `self` first, then `other`. Only pairs of states
+    /// reachable from the start are built.
     pub fn product(&self, other: &Transducer) -> Self {
+        assert_eq!(self.alphabet(), other.alphabet());
+        let k = self.alphabet();
+        let m = other.transition.len();
+        let mut index = vec![usize::MAX; self.transition.len() * m];
+        let mut pairs = vec![(0, 0)];
         let mut new_transition = Vec::new();
-        let mut new_flip = Vec::new();
-        for i in 0..self.transition.len() {
-            for j in 0..other.transition.len() {
-                let mut nt = Vec::new();
-                new_flip.push((self.flip[i] + other.flip[j]) % 2);
-                for k in 0..2 {
-                    nt.push(
-                        self.transition[i][k] * other.transition.len()
-                            + other.transition[j][(k + (self.flip[i] as usize)) % 2],
-                    );
+        let mut new_output = Vec::new();
+        index[0] = 0;
+        let mut next = 0;
+        while next < pairs.len() {
+            let (i, j) = pairs[next];
+            for a in 0..k {
+                let b = self.output[i][a] as usize;
+                let pair = (self.transition[i][a], other.transition[j][b]);
+                let key = pair.0 * m + pair.1;
+                if index[key] == usize::MAX {
+                    index[key] = pairs.len();
+                    pairs.push(pair);
                 }
-                new_transition.push(nt);
+                new_transition.push(index[key]);
+                new_output.push(other.output[j][b]);
             }
+            next += 1;
         }
-        return Transducer {
-            transition: new_transition,
-            flip: new_flip,
-        }
-        .minimize();
+        return minimize_flat(k, &new_transition, &new_output);
     }
 
     /// Create a graph corresponding to the transducer's structure.
+    ///
+    /// Active states, which move some letter, are drawn as diamonds. On
+    /// alphabets larger than two, edges are also labelled with the letter
+    /// written.
     pub fn graph(&self) -> Graph {
         let mut res = graph!(strict di id!());
         for i in 0..self.transition.len() {
-            if self.flip[i] > 0 {
+            if self.active(i) {
                 res.add_stmt(stmt!(node!(i; attr!("shape", "diamond"))));
             } else {
                 res.add_stmt(stmt!(node!(i; attr!("shape", "circle"))));
             }
-            if self.transition[i][0] == self.transition[i][1] {
+            if self.transition[i]
+                .iter()
+                .all(|&t| t == self.transition[i][0])
+            {
                 res.add_stmt(stmt!(
                     edge!(node_id!(i) => node_id!(self.transition[i][0]); attr!("label", " a"))
                 ));
             } else {
-                for j in 0..2 {
+                for j in 0..self.alphabet() {
+                    let label = if self.alphabet() == 2 {
+                        format!(" {}", j)
+                    } else {
+                        format!(" {}|{}", j, self.output[i][j])
+                    };
                     res.add_stmt(stmt!(
-                    edge!(node_id!(i) => node_id!(self.transition[i][j]); attr!("label", (format!(" {}", j))))
-                ));
+                        edge!(node_id!(i) => node_id!(self.transition[i][j]); attr!("label", label))
+                    ));
                 }
             }
         }
         return res;
     }
 
-    /// TODO
-    pub fn detailed_orbit_tree(&self, depth: usize) {
-        let mut cycle_trace = FxHashMap::default();
-        let mut prev: Vec<Vec<u8>> = vec![Vec::new()];
-        for _ in 1..depth + 1 {
-            let mut new_prev = Vec::new();
-            for word in &prev {
-                let mut target0 = word.clone();
-                let mut target1 = word.clone();
-                target0.push(0);
-                target1.push(1);
-                let mut seen1 = false;
-                let mut v = target0.clone();
-                let mut res = Vec::new();
-                new_prev.push(target0.clone());
-                loop {
-                    res.push(self.step(&mut v));
-                    if v == target1 {
-                        seen1 = true;
+    /// Create the transducer's orbit tree up to a given `depth`, recording
+    /// for each orbit its length and the states visited along its cycle.
+    pub fn detailed_orbit_tree(&self, depth: usize) -> OrbitTree {
+        self.assert_invertible();
+        return OrbitTree::new(self, depth);
+    }
+
+    /// Create a graph corresponding to the transducer's orbit tree up to a
+    /// given `depth`.
+    pub fn orbit_tree(&self, depth: usize) -> Graph {
+        let mut res = graph!(strict di id!();
+          node!(esc ""; attr!("label", "\u{03b5}"), attr!("shape", "circle"), attr!("root", "true")));
+        let tree = OrbitTree::new(self, depth);
+        for node in tree.iter() {
+            let wlabel = repr(&node.word);
+            for child in tree.children(node) {
+                let c_label = repr(&child.word);
+                res.add_stmt(stmt!(node!(esc c_label; attr!("shape", "circle"))));
+                res.add_stmt(stmt!(edge!(node_id!(esc wlabel) => node_id!(esc c_label))));
+            }
+        }
+        return res;
+    }
+
+    /// The state reached from `state` after reading `word`.
+    pub fn section(&self, state: usize, word: &[u8]) -> usize {
+        let mut state = state;
+        for &c in word {
+            state = self.transition[state][c as usize];
+        }
+        return state;
+    }
+
+    /// The automorphism `g|w` applied below `word`, as a minimized
+    /// transducer started at the state reached after reading `word`.
+    pub fn restriction(&self, word: &[u8]) -> Self {
+        return self.renumber(self.section(0, word)).minimize();
+    }
+
+    /// The output map of the start state, that is, the permutation `σ` in
+    /// the wreath recursion `g = (g|0, ..., g|k-1)σ`.
+    pub fn root(&self) -> &[u8] {
+        return &self.output[0];
+    }
+
+    /// The restrictions `g|0, ..., g|k-1` at each letter, as in the wreath
+    /// recursion `g = (g|0, ..., g|k-1)σ`.
+    pub fn sections(&self) -> Vec<Self> {
+        return (0..self.alphabet() as u8)
+            .map(|a| self.restriction(&[a]))
+            .collect();
+    }
+
+    /// The wreath recursion of every state `s`, as the successors
+    /// `(s|0, ..., s|k-1)` and the output map `σ` with `s = (s|0, ..., s|k-1)σ`.
+    pub fn wreath_recursion(&self) -> Vec<(Vec<usize>, Vec<u8>)> {
+        return self
+            .transition
+            .iter()
+            .cloned()
+            .zip(self.output.iter().cloned())
+            .collect();
+    }
+
+    /// For each letter `a`, the restriction `g^m|a` of the smallest power of
+    /// `g` fixing `a`, where `m` is the length of the cycle of the root
+    /// permutation through `a`.
+    ///
+    /// The residue applies `g|a`, then `g|σ(a)`, and so on up to
+    /// `g|σ^(m-1)(a)`. The orbits of `g` below the cycle of `a` correspond to
+    /// the orbits of the residue below `a`.
+    pub fn residues(&self) -> Vec<Self> {
+        self.assert_invertible();
+        return (0..self.alphabet()).map(|a| self.residue(a)).collect();
+    }
+
+    fn residue(&self, a: usize) -> Self {
+        let mut acc = self.restriction(&[a as u8]);
+        let mut b = self.output[0][a] as usize;
+        while b != a {
+            acc = acc.product(&self.restriction(&[b as u8]));
+            b = self.output[0][b] as usize;
+        }
+        return acc;
+    }
+
+    /// Test whether the transducer acts transitively on each level up to
+    /// `depth`.
+    ///
+    /// Given transitivity on level `n - 1`, the action is transitive on level
+    /// `n` exactly when `g^(k^(n-1))` permutes the letters below `0^(n-1)` in
+    /// a single `k`-cycle. That power is tracked as the sequence of states it
+    /// passes through, so this takes O(k^depth) time. Returns these cycles as
+    /// a certificate, or the first level where transitivity fails.
+    pub fn is_level_transitive(&self, depth: usize) -> Result<Vec<Vec<u8>>, usize> {
+        self.assert_invertible();
+        let k = self.alphabet();
+        let mut certificate = Vec::new();
+        let mut states = vec![0];
+        for level in 1..depth + 1 {
+            let cycle: Vec<u8> = (0..k)
+                .map(|a| states.iter().fold(a, |x, &s| self.output[s][x] as usize) as u8)
+                .collect();
+            let mut len = 1;
+            let mut x = cycle[0] as usize;
+            while x != 0 {
+                len += 1;
+                x = cycle[x] as usize;
+            }
+            if len != k {
+                return Err(level);
+            }
+            let mut next = Vec::with_capacity(k * states.len());
+            let mut x = 0;
+            for _ in 0..k {
+                for &s in &states {
+                    next.push(self.transition[s][x]);
+                    x = self.output[s][x] as usize;
+                }
+            }
+            certificate.push(cycle);
+            states = next;
+        }
+        return Ok(certificate);
+    }
+
+    /// Test whether a binary transducer acts transitively on every level.
+    ///
+    /// Let `t_n` count, modulo 2, the words of length `n` leading to each
+    /// state. Given transitivity on level `n`, the action is transitive on
+    /// level `n + 1` exactly when an odd number of those words lead to active
+    /// states. The sequence `t_n` is eventually periodic, so this decides
+    /// transitivity on all levels. Returns the sequence up to its first
+    /// repetition as a certificate, or the first level where transitivity
+    /// fails.
+    pub fn is_spherically_transitive(&self) -> Result<Vec<Vec<u8>>, usize> {
+        assert_eq!(self.alphabet(), 2);
+        self.assert_invertible();
+        let mut trace: Vec<u8> = vec![0; self.transition.len()];
+        let mut seen: FxHashSet<Vec<u8>> = FxHashSet::default();
+        let mut certificate = Vec::new();
+        trace[0] = 1;
+        loop {
+            let parity = (0..self.transition.len())
+                .filter(|&s| trace[s] == 1 && self.active(s))
+                .count()
+                % 2;
+            if parity == 0 {
+                return Err(certificate.len() + 1);
+            }
+            if !seen.insert(trace.clone()) {
+                return Ok(certificate);
+            }
+            let mut next = vec![0; self.transition.len()];
+            for (s, t) in self.transition.iter().enumerate() {
+                for &s2 in t {
+                    next[s2] ^= trace[s];
+                }
+            }
+            certificate.push(trace);
+            trace = next;
+        }
+    }
+
+    /// The cycles of the start state's output permutation, each given by its
+    /// smallest letter and its length.
+    fn root_cycles(&self) -> Vec<(usize, usize)> {
+        let mut seen = vec![false; self.alphabet()];
+        let mut res = Vec::new();
+        for a in 0..self.alphabet() {
+            let mut len = 0;
+            let mut b = a;
+            while !seen[b] {
+                seen[b] = true;
+                len += 1;
+                b = self.output[0][b] as usize;
+            }
+            if len > 0 {
+                res.push((a, len));
+            }
+        }
+        return res;
+    }
+
+    /// Explore the graph of residues from the transducer. Each node is a
+    /// minimized residue `h`, with an edge `(a, v, m)` for each cycle of the
+    /// root permutation of `h`, of length `m` with smallest letter `a`, to
+    /// the node `v` of the residue of `h` at `a`. Also returns whether the
+    /// graph closed within the budget.
+    fn residue_graph(&self, budget: &Budget) -> (Vec<Self>, ResidueEdges, bool) {
+        let mut index = FxHashMap::default();
+        let mut nodes = vec![self.minimize()];
+        let mut edges: ResidueEdges = Vec::new();
+        index.insert(nodes[0].clone(), 0);
+        let mut closed = true;
+        while edges.len() < nodes.len() {
+            let h = nodes[edges.len()].clone();
+            let residues = h.residues();
+            let mut out = Vec::new();
+            for (a, m) in h.root_cycles() {
+                let r = residues[a].clone();
+                if r.len() > budget.states
+                    || (!index.contains_key(&r) && nodes.len() >= budget.elements)
+                {
+                    closed = false;
+                    continue;
+                }
+                let next = *index.entry(r.clone()).or_insert(nodes.len());
+                if next == nodes.len() {
+                    nodes.push(r);
+                }
+                out.push((a, next, m));
+            }
+            edges.push(out);
+        }
+        return (nodes, edges, closed);
+    }
+
+    /// Compute the order of the automorphism defined by the transducer.
+    ///
+    /// If a letter lies in a cycle of length `m` at the root, the order is a
+    /// multiple of `m` times the order of the residue of the `m`th power at
+    /// that letter. This explores the graph of such residues: a cycle in it
+    /// through some `m > 1` proves the order infinite, and otherwise a closed
+    /// graph determines the order exactly.
+    pub fn order(&self, budget: &Budget) -> Order {
+        let (nodes, edges, closed) = self.residue_graph(budget);
+
+        // A cycle through an edge of weight m > 1 makes the order infinite.
+        for u in 0..nodes.len() {
+            for &(_, v, m) in &edges[u] {
+                if m == 1 {
+                    continue;
+                }
+                let mut seen = vec![false; nodes.len()];
+                let mut frontier = vec![v];
+                seen[v] = true;
+                while let Some(x) = frontier.pop() {
+                    if x == u {
+                        return Order::Infinite;
                     }
-                    if v == target0 {
-                        break;
+                    for &(_, y, _) in &edges[x] {
+                        if !seen[y] {
+                            seen[y] = true;
+                            frontier.push(y);
+                        }
                     }
                 }
-                cycle_trace.insert(target0, res);
-                if !seen1 {
-                    new_prev.push(target1.clone());
-                    res = Vec::new();
-                    let mut v = target1.clone();
-                    loop {
-                        res.push(self.step(&mut v));
-                        if v == target1 {
-                            break;
-                        }
+            }
+        }
+        if !closed {
+            return Order::Unknown;
+        }
+
+        // Otherwise the orders are the least fixed point of the recursion.
+        let mut orders = vec![1usize; nodes.len()];
+        loop {
+            let mut changed = false;
+            for u in (0..nodes.len()).rev() {
+                let mut o = 1;
+                for &(_, v, m) in &edges[u] {
+                    let lcm = orders[v]
+                        .checked_mul(m)
+                        .and_then(|x| (o / o.gcd(&x)).checked_mul(x));
+                    match lcm {
+                        Some(x) => o = x,
+                        None => return Order::Unknown,
                     }
-                    cycle_trace.insert(target1, res);
+                }
+                if o != orders[u] {
+                    orders[u] = o;
+                    changed = true;
                 }
             }
-            prev = new_prev;
+            if !changed {
+                return Order::Finite(orders[0]);
+            }
         }
-        let mut out = cycle_trace.into_iter().collect::<Vec<_>>();
-        out.sort_by(|(x, _), (y, _)| match x.len().cmp(&y.len()) {
-            std::cmp::Ordering::Equal => x.cmp(&y),
-            other => other,
-        });
-        for elem in out {
-            let r0 = elem
-                .0
-                .iter()
-                .map(|&x| (('0' as u8) + x) as char)
-                .collect::<String>();
-            let r1 = elem
-                .1
-                .iter()
-                .map(|&x| (('A' as u8) + (x as u8)) as char)
-                /* .map(|x| match x {
-                    'A' => '$',
-                    'B' => '.',
-                    _ => '!',
-                }) */
-                .collect::<String>();
-            println!("{:depth$} {}", r0, r1);
+    }
+
+    /// Compute a DFA recognizing the words which are least in their orbit.
+    ///
+    /// A word `aw` is least in its orbit exactly when `a` is the smallest
+    /// letter of its cycle at the root, of length `m` say, and `w` is least in
+    /// its orbit under the residue `g^m|a`. So the graph of residues, with
+    /// the edges labelled by these letters, is such a DFA when it is finite.
+    /// Returns its canonical form, or `None` if the graph does not close
+    /// within the budget.
+    pub fn orbit_language(&self, budget: &Budget) -> Option<DFA<u8>> {
+        self.assert_invertible();
+        let (nodes, edges, closed) = self.residue_graph(budget);
+        if !closed {
+            return None;
         }
+        let alphabet = (0..self.alphabet() as u8).collect();
+        let transition = edges
+            .iter()
+            .map(|out| out.iter().map(|&(a, v, _)| (a as u8, v)).collect())
+            .collect();
+        return Some(DFA::new(alphabet, transition, vec![true; nodes.len()]).canonicalize());
     }
+}
 
-    /// Create a graph corresponding to the transducer's orbit tree up to a
-    /// given `depth`.
-    pub fn orbit_tree(&self, depth: usize) -> Graph {
-        let mut res = graph!(strict di id!();
-          node!(esc ""; attr!("label", "\u{03b5}"), attr!("shape", "circle"), attr!("root", "true")));
-        let mut words = vec![Vec::new()];
-        for _ in 0..depth {
-            let mut new_words = vec![];
-            for word in words {
-                let mut l = word.clone();
-                let mut r = word.clone();
-                l.push(0);
-                r.push(1);
-                let wlabel = repr(&word);
-                let l_label = repr(&l);
-                let r_label = repr(&r);
-                new_words.push(l.clone());
-                res.add_stmt(stmt!(node!(esc l_label; attr!("shape", "circle"))));
-                res.add_stmt(stmt!(edge!(node_id!(esc wlabel) => node_id!(esc l_label))));
-                if self.min_word(&r) != l {
-                    new_words.push(r.clone());
-                    res.add_stmt(stmt!(node!(esc r_label; attr!("shape", "circle"))));
-                    res.add_stmt(stmt!(edge!(node_id!(esc wlabel) => node_id!(esc r_label))));
-                }
+/// Minimize the transducer on the alphabet `0..k` where state `s` moves to
+/// `transition[s * k + a]` and writes `output[s * k + a]` on reading `a`.
+///
+/// Unreachable states are dropped, and the result is numbered in
+/// breadth-first order from the start state.
+fn minimize_flat(k: usize, transition: &[usize], output: &[u8]) -> Transducer {
+    let n = transition.len() / k;
+    let mut states = vec![0];
+    let mut seen = vec![false; n];
+    seen[0] = true;
+    let mut next = 0;
+    while next < states.len() {
+        for &s2 in &transition[states[next] * k..states[next] * k + k] {
+            if !seen[s2] {
+                seen[s2] = true;
+                states.push(s2);
             }
-            words = new_words;
         }
-        return res;
+        next += 1;
     }
 
-    /// TODO
-    pub fn residues(&self) -> Vec<Self> {
-        let mut remap_l = FxHashMap::default();
-        let mut remap_r = FxHashMap::default();
-        for i in 0..self.transition.len() {
-            remap_l.insert(i, i);
-            remap_r.insert(i, i);
-        }
-        remap_l.insert(0, self.transition[0][0]);
-        remap_r.insert(0, self.transition[0][1]);
-        remap_l.insert(self.transition[0][0], 0);
-        remap_r.insert(self.transition[0][1], 0);
-        let l = self.relabel(remap_l);
-        let r = self.relabel(remap_r);
-        if self.flip[0] == 0 {
-            return vec![l.minimize(), r.minimize()];
-        } else {
-            return vec![l.product(&r).minimize(), r.product(&l).minimize()];
+    // Start with the classes of states with equal outputs, then refine by
+    // the classes of successors until nothing changes.
+    let mut class = vec![0; n];
+    let mut order = states.clone();
+    order.sort_by(|&x, &y| output[x * k..x * k + k].cmp(&output[y * k..y * k + k]));
+    let mut count = 0;
+    for (i, &s) in order.iter().enumerate() {
+        if i > 0 && output[s * k..s * k + k] != output[order[i - 1] * k..order[i - 1] * k + k] {
+            count += 1;
         }
+        class[s] = count;
     }
+    count += 1;
+    let mut signature = vec![0; n * (k + 1)];
+    loop {
+        for &s in &states {
+            signature[s * (k + 1)] = class[s];
+            for a in 0..k {
+                signature[s * (k + 1) + a + 1] = class[transition[s * k + a]];
+            }
+        }
+        let key = |s: usize| &signature[s * (k + 1)..(s + 1) * (k + 1)];
+        order.sort_by(|&x, &y| key(x).cmp(key(y)));
+        let mut new_count = 0;
+        for (i, &s) in order.iter().enumerate() {
+            if i > 0 && key(s) != key(order[i - 1]) {
+                new_count += 1;
+            }
+            class[s] = new_count;
+        }
+        new_count += 1;
+        if new_count == count {
+            break;
+        }
+        count = new_count;
+    }
+
+    // Number the classes in breadth-first order, each represented by its
+    // first state in that order.
+    let mut number = vec![usize::MAX; count];
+    let mut representatives = vec![0];
+    number[class[0]] = 0;
+    let mut next = 0;
+    while next < representatives.len() {
+        let s = representatives[next];
+        for &s2 in &transition[s * k..s * k + k] {
+            if number[class[s2]] == usize::MAX {
+                number[class[s2]] = representatives.len();
+                representatives.push(s2);
+            }
+        }
+        next += 1;
+    }
+    return Transducer {
+        transition: representatives
+            .iter()
+            .map(|&s| {
+                transition[s * k..s * k + k]
+                    .iter()
+                    .map(|&t| number[class[t]])
+                    .collect()
+            })
+            .collect(),
+        output: representatives
+            .iter()
+            .map(|&s| output[s * k..s * k + k].to_vec())
+            .collect(),
+    };
 }
 
+/// The edges `(letter, target, cycle length)` out of each node of a graph of
+/// residues.
+type ResidueEdges = Vec<Vec<(usize, usize, usize)>>;
+
+type TransducerCycle = TCycle<
+    Vec<usize>,
+    Vec<Vec<usize>>,
+    VCycle<usize, UCycle<usize>>,
+    VCycle<Vec<usize>, VCycle<usize, UCycle<usize>>>,
+>;
+
 /// An iterator through all transducers of a particular size.
 pub struct AllTransducers {
-    state: (
-        usize,
-        TCycle<
-            Vec<u8>,
-            Vec<Vec<usize>>,
-            VCycle<u8, UCycle<u8>>,
-            VCycle<Vec<usize>, VCycle<usize, UCycle<usize>>>,
-        >,
-    ),
+    alphabet: usize,
+    outputs: Vec<Vec<u8>>,
+    state: (usize, TransducerCycle),
 }
 
 impl AllTransducers {
-    /// Create a new iterator through all transducers of a particular `size`.
+    /// Create a new iterator through all binary transducers, in increasing
+    /// order of size.
     pub fn new() -> AllTransducers {
+        return AllTransducers::with_alphabet(2);
+    }
+
+    /// Create a new iterator through all transducers on the alphabet `0..k`,
+    /// in increasing order of size.
+    pub fn with_alphabet(k: usize) -> AllTransducers {
+        let outputs = Permutation::new(k)
+            .map(|p| p.into_iter().map(|x| x as u8).collect())
+            .collect();
+        return AllTransducers::with_outputs(k, outputs);
+    }
+
+    /// Create a new iterator through all Mealy machines on the alphabet
+    /// `0..k`, including non-invertible ones, in increasing order of size.
+    pub fn mealy(k: usize) -> AllTransducers {
+        let outputs = VCycle::<usize, UCycle<usize>>::new(vec![k; k], true)
+            .map(|p| p.into_iter().map(|x| x as u8).collect())
+            .collect();
+        return AllTransducers::with_outputs(k, outputs);
+    }
+
+    fn with_outputs(k: usize, outputs: Vec<Vec<u8>>) -> AllTransducers {
         AllTransducers {
-            state: (1, TCycle::new((vec![2; 1], vec![vec![1; 2]; 1]), true)),
+            alphabet: k,
+            state: (
+                1,
+                TCycle::new((vec![outputs.len(); 1], vec![vec![1; k]; 1]), true),
+            ),
+            outputs,
         }
     }
 }
 
+impl Default for AllTransducers {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
 impl Iterator for AllTransducers {
     type Item = Transducer;
 
@@ -418,20 +1089,23 @@ impl Iterator for AllTransducers {
                 self.state.0 += 1;
                 self.state.1 = TCycle::new(
                     (
-                        vec![2; self.state.0],
-                        vec![vec![self.state.0; 2]; self.state.0],
+                        vec![self.outputs.len(); self.state.0],
+                        vec![vec![self.state.0; self.alphabet]; self.state.0],
                     ),
                     true,
                 );
                 self.next()
             }
-            Some((flip, transition)) => Some(Transducer {
+            Some((output, transition)) => Some(Transducer {
                 transition: transition
                     .into_iter()
                     .rev()
                     .map(|x| x.into_iter().rev().collect())
                     .collect(),
-                flip: flip.into_iter().map(|x| 1 - x).collect(),
+                output: output
+                    .into_iter()
+                    .map(|x| self.outputs[self.outputs.len() - 1 - x].clone())
+                    .collect(),
             }),
         }
     }
@@ -439,58 +1113,27 @@ impl Iterator for AllTransducers {
 
 fn distinguish(class: &FxHashSet<Transducer>, depth: usize) -> Vec<FxHashSet<Transducer>> {
     let mut res: Vec<FxHashSet<Transducer>> = Vec::new();
-    let mut remainder: Vec<_> = class.into_iter().collect();
+    let mut remainder: Vec<_> = class.iter().collect();
     loop {
-        if remainder.len() == 0 {
+        if remainder.is_empty() {
             break;
         }
         let cand = remainder.pop().unwrap();
         let (mut l, r): (Vec<_>, Vec<_>) = remainder
             .into_par_iter()
-            .partition(|x| cand.orbit_compare(x, depth));
+            .partition(|x| cand.orbit_equal(x, depth));
         l.push(cand);
-        res.push(l.into_iter().map(|x| x.clone()).collect());
+        res.push(l.into_iter().cloned().collect());
         remainder = r;
     }
     return res;
 }
 /*
-/// Determine whether a transducer's orbit is the maximal cycle.
-fn maximal(m: &Transducer) -> bool {
-    let mut trace: Vec<u8> = vec![0; m.transition.len()];
-    let mut seen: FxHashSet<Vec<u8>> = FxHashSet::default();
-    trace[0] = 1;
-    loop {
-        if trace
-            .iter()
-            .zip(m.flip.iter())
-            .map(|(a, b)| a * b)
-            .fold(0, |a, b| a + b)
-            % 2
-            == 0
-        {
-            return false;
-        }
-        if seen.contains(&trace) {
-            return true;
-        }
-        let mut next = vec![0; m.transition.len()];
-        for i in 0..m.transition.len() {
-            for j in 0..2 {
-                next[m.transition[i][j]] += 1 * trace[i];
-                next[m.transition[i][j]] %= 2;
-            }
-        }
-        seen.insert(trace.clone());
-        trace = next;
-    }
-}
-
 fn finitely_branching(m: &Transducer, depth: usize) -> bool {
     if depth == 0 {
-        return maximal(m);
+        return m.is_spherically_transitive().is_ok();
     } else {
-        return maximal(m)
+        return m.is_spherically_transitive().is_ok()
             || m.residues()
                 .iter()
                 .all(|m| finitely_branching(m, depth - 1));
@@ -570,7 +1213,7 @@ pub fn classify_transducers(depth: usize) -> Vec<FxHashSet<Transducer>> {
         }
     }
     /*
-    let preds = vec![maximal];
+    let preds = vec![|m: &&Transducer| m.is_spherically_transitive().is_ok()];
     for (i, pred) in preds.iter().enumerate() {
         let (l, r): (Vec<_>, Vec<_>) = initial.into_par_iter().partition(pred);
         if l.len() > 0 {
@@ -636,3 +1279,268 @@ pub fn classify_transducers(depth: usize) -> Vec<FxHashSet<Transducer>> {
     classes.append(&mut pre_classes);
     return classes;
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// All words on the alphabet `0..k` of length at most `max_len`.
+    fn words(k: usize, max_len: usize) -> Vec<Vec<u8>> {
+        let mut res = vec![Vec::new()];
+        for n in 1..=max_len {
+            res.extend(VCycle::<u8, UCycle<u8>>::new(vec![k as u8; n], true));
+        }
+        return res;
+    }
+
+    fn apply(m: &Transducer, word: &[u8]) -> Vec<u8> {
+        let mut res = word.to_vec();
+        m.step(&mut res);
+        return res;
+    }
+
+    /// A sample of transducers on the alphabet `0..k`, with a machine
+    /// merging letters for each, so that non-invertible ones are included.
+    fn sample(k: usize, n: usize, step: usize) -> Vec<Transducer> {
+        let mut res = Vec::new();
+        for m in AllTransducers::with_alphabet(k).take(n).step_by(step) {
+            let output = m
+                .output
+                .iter()
+                .map(|o| o.iter().map(|&b| b / 2).collect())
+                .collect();
+            res.push(Transducer::with_output(m.transition.clone(), output));
+            res.push(m);
+        }
+        return res;
+    }
+
+    #[test]
+    fn minimize_preserves_action() {
+        for (k, max_len, n) in [(2, 6, 6000), (3, 4, 3000)] {
+            let words = words(k, max_len);
+            for m in sample(k, n, 7) {
+                let min = m.minimize();
+                assert!(min.len() <= m.len());
+                for word in &words {
+                    assert_eq!(apply(&min, word), apply(&m, word), "{:?}", m);
+                }
+            }
+        }
+    }
+
+    #[test]
+    fn order_matches_powers() {
+        let budget = Budget {
+            elements: 200,
+            states: 16,
+        };
+        let mut finite = 0;
+        for (k, n) in [(2, 3000), (3, 2000)] {
+            for m in AllTransducers::with_alphabet(k).take(n).step_by(29) {
+                // The least power up to 64 which is the identity, giving up
+                // once the powers grow large.
+                let mut power = m.minimize();
+                let mut brute = None;
+                let mut complete = true;
+                for i in 1..=64 {
+                    if power.is_identity() {
+                        brute = Some(i);
+                        break;
+                    }
+                    if power.len() > 32 {
+                        complete = false;
+                        break;
+                    }
+                    power = power.product(&m);
+                }
+                match m.order(&budget) {
+                    Order::Finite(o) => {
+                        assert!(brute == Some(o) || o > 64 || !complete, "{:?}", m);
+                        finite += 1;
+                    }
+                    Order::Infinite => assert_eq!(brute, None, "{:?}", m),
+                    Order::Unknown => (),
+                }
+            }
+        }
+        assert!(finite > 0);
+    }
+
+    #[test]
+    fn orbit_language_accepts_orbit_minima() {
+        let budget = Budget {
+            elements: 200,
+            states: 16,
+        };
+        let mut exact = 0;
+        for (k, n, max_len) in [(2, 3000, 6), (3, 2000, 4)] {
+            let words = words(k, max_len);
+            for m in AllTransducers::with_alphabet(k).take(n).step_by(29) {
+                if let Some(dfa) = m.orbit_language(&budget) {
+                    for word in &words {
+                        assert_eq!(dfa.accepts(word), &m.min_word(word) == word, "{:?}", m);
+                    }
+                    exact += 1;
+                }
+            }
+        }
+        assert!(exact > 0);
+    }
+
+    #[test]
+    fn product_applies_in_order() {
+        for (k, max_len, n) in [(2, 6, 3000), (3, 4, 2000)] {
+            let words = words(k, max_len);
+            let ms = sample(k, n, 53);
+            for a in &ms {
+                for b in &ms {
+                    let p = a.product(b);
+                    for word in &words {
+                        assert_eq!(apply(&p, word), apply(b, &apply(a, word)));
+                    }
+                }
+            }
+        }
+    }
+
+    /// Whether every state is reachable from the start state.
+    fn accessible(m: &Transducer) -> bool {
+        let mut seen = vec![false; m.len()];
+        let mut frontier = vec![0];
+        seen[0] = true;
+        while let Some(s) = frontier.pop() {
+            for &t in &m.transition[s] {
+                if !seen[t] {
+                    seen[t] = true;
+                    frontier.push(t);
+                }
+            }
+        }
+        return seen.iter().all(|&x| x);
+    }
+
+    /// Canonicalize by trying every relabelling fixing the start state, as
+    /// before breadth-first renumbering.
+    fn canonicalize_by_permutation(m: &Transducer) -> Transducer {
+        let mut min = m.clone();
+        for p in Permutation::new(m.len() - 1) {
+            let mut map = FxHashMap::default();
+            map.insert(0, 0);
+            for (i, j) in p.iter().enumerate() {
+                map.insert(i + 1, j + 1);
+            }
+            let cand = m.relabel(map);
+            if m.is_invertible() {
+                min = min.min(cand.inverse());
+            }
+            min = min.min(cand);
+        }
+        return min;
+    }
+
+    #[test]
+    fn canonicalize_matches_permutations() {
+        let mut by_bfs: FxHashMap<Transducer, Transducer> = FxHashMap::default();
+        let mut by_permutation: FxHashMap<Transducer, Transducer> = FxHashMap::default();
+        for m in AllTransducers::new().take_while(|m| m.len() <= 3) {
+            if !accessible(&m) {
+                continue;
+            }
+            let (a, b) = (m.canonicalize(), canonicalize_by_permutation(&m));
+            assert_eq!(by_bfs.entry(a.clone()).or_insert(b.clone()), &b);
+            assert_eq!(by_permutation.entry(b).or_insert(a.clone()), &a);
+        }
+        assert_eq!(by_bfs.len(), by_permutation.len());
+    }
+
+    #[test]
+    fn orbit_equal_matches_orbit_compare() {
+        let mut equal = 0;
+        for (k, max_depth, n, step) in [(2, 8, 4000, 97), (3, 4, 3000, 131)] {
+            let ms: Vec<Transducer> = AllTransducers::with_alphabet(k)
+                .take(n)
+                .step_by(step)
+                .filter(|m| m.is_invertible())
+                .collect();
+            for a in &ms {
+                let mut others = ms.clone();
+                others.push(a.inverse());
+                others.push(a.product(a));
+                for b in &others {
+                    for depth in 0..=max_depth {
+                        let brute = VCycle::<u8, UCycle<u8>>::new(vec![k as u8; depth], true)
+                            .all(|w| a.min_word(&w) == b.min_word(&w));
+                        assert_eq!(a.orbit_compare(b, depth), brute, "{:?} {:?}", a, b);
+                        assert_eq!(a.orbit_equal(b, depth), brute, "{:?} {:?}", a, b);
+                        equal += (brute && depth == max_depth) as usize;
+                    }
+                }
+            }
+        }
+        assert!(equal > 0);
+    }
+
+    #[test]
+    fn alphabet_sizes() {
+        let id = Transducer::identity(255);
+        assert_eq!(id.sections().len(), 255);
+        let swap = Transducer::with_output(vec![vec![0; 255]], vec![(0..255).rev().collect()]);
+        assert!(!swap.orbit_equal(&id, 1));
+        assert!(!swap.is_identity());
+    }
+
+    #[test]
+    #[should_panic(expected = "alphabet size 0")]
+    fn empty_alphabet() {
+        Transducer::with_output(vec![vec![]], vec![vec![]]);
+    }
+
+    #[test]
+    #[should_panic(expected = "alphabet size 256")]
+    fn alphabet_too_large() {
+        Transducer::identity(256);
+    }
+
+    #[test]
+    fn orbit_equivalence_proofs() {
+        let budget = Budget {
+            elements: 2000,
+            states: 16,
+        };
+        let (mut equal, mut distinct) = (0, 0);
+        for (k, max_depth, n, step) in [(2, 8, 4000, 97), (3, 4, 3000, 131)] {
+            let words = words(k, max_depth);
+            let ms: Vec<Transducer> = AllTransducers::with_alphabet(k)
+                .take(n)
+                .step_by(step)
+                .collect();
+            for a in &ms {
+                let mut others = ms.clone();
+                others.push(a.inverse());
+                others.push(a.product(a));
+                for b in &others {
+                    match a.orbit_equivalence(b, &budget) {
+                        OrbitEquivalence::Equal(t) => {
+                            for depth in 0..=max_depth {
+                                assert!(a.orbit_compare(b, depth), "{:?} {:?}", a, b);
+                            }
+                            for word in &words {
+                                assert_eq!(apply(&t, word), a.min_word(word), "{:?} {:?}", a, b);
+                            }
+                            equal += 1;
+                        }
+                        OrbitEquivalence::Distinct(w) => {
+                            assert_ne!(a.min_word(&w), b.min_word(&w), "{:?} {:?}", a, b);
+                            assert!(!a.orbit_compare(b, w.len()), "{:?} {:?}", a, b);
+                            assert!(a.orbit_compare(b, w.len() - 1), "{:?} {:?}", a, b);
+                            distinct += 1;
+                        }
+                        OrbitEquivalence::Unknown => (),
+                    }
+                }
+            }
+        }
+        assert!(equal > 0 && distinct > 0);
+    }
+}
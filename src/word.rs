@@ -0,0 +1,184 @@
+/// A binary word packed into 64-bit limbs.
+///
+/// Bits are stored most significant first, and unused bits are zero, so words
+/// of equal length compare in lexicographic order.
+#[derive(Clone, Debug, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub struct PackedWord {
+    limbs: Vec<u64>,
+    len: usize,
+}
+
+impl PackedWord {
+    /// Create the word of `len` zeros.
+    pub fn new(len: usize) -> Self {
+        PackedWord {
+            limbs: vec![0; len.div_ceil(64)],
+            len,
+        }
+    }
+
+    /// Pack a word of bits.
+    pub fn from_word(word: &[u8]) -> Self {
+        let mut res = PackedWord::new(word.len());
+        for (i, &b) in word.iter().enumerate() {
+            res.set(i, b);
+        }
+        return res;
+    }
+
+    /// Create the word of length `len` whose bits are the low `len` bits of
+    /// `x`, most significant first.
+    pub fn from_bits(x: u64, len: usize) -> Self {
+        assert!(len <= 64);
+        let mut res = PackedWord::new(len);
+        if len > 0 {
+            res.limbs[0] = x << (64 - len);
+        }
+        return res;
+    }
+
+    /// Unpack the word into one byte per bit.
+    pub fn to_word(&self) -> Vec<u8> {
+        return (0..self.len).map(|i| self.get(i)).collect();
+    }
+
+    /// The length of the word.
+    pub fn len(&self) -> usize {
+        return self.len;
+    }
+
+    /// Whether the word is empty.
+    pub fn is_empty(&self) -> bool {
+        return self.len == 0;
+    }
+
+    /// The bit at position `i`.
+    pub fn get(&self, i: usize) -> u8 {
+        assert!(i < self.len);
+        return (self.limbs[i / 64] >> (63 - i % 64)) as u8 & 1;
+    }
+
+    /// Set the bit at position `i` to `b`.
+    pub fn set(&mut self, i: usize, b: u8) {
+        assert!(i < self.len && b < 2);
+        let mask = 1 << (63 - i % 64);
+        if b == 1 {
+            self.limbs[i / 64] |= mask;
+        } else {
+            self.limbs[i / 64] &= !mask;
+        }
+    }
+}
+
+/// Lookup tables applying a binary transducer to a byte at a time.
+///
+/// For each state and byte, the table holds the byte written and the state
+/// reached, so a packed word is transformed in one lookup per 8 bits.
+#[derive(Clone, Debug)]
+pub struct StepTable {
+    byte_output: Vec<u8>,
+    byte_next: Vec<usize>,
+    bit_next: Vec<usize>,
+}
+
+impl StepTable {
+    /// Build the tables of a binary transducer with `n` states, where state
+    /// `s` reading `b` writes and moves to `step(s, b)`.
+    pub fn new(n: usize, step: impl Fn(usize, u8) -> (u8, usize)) -> Self {
+        let mut bit_output = Vec::with_capacity(2 * n);
+        let mut bit_next = Vec::with_capacity(2 * n);
+        for s in 0..n {
+            for b in 0..2 {
+                let (o, t) = step(s, b);
+                bit_output.push(o);
+                bit_next.push(t);
+            }
+        }
+        let mut byte_output = vec![0; 256 * n];
+        let mut byte_next = vec![0; 256 * n];
+        for s in 0..n {
+            for x in 0..256 {
+                let mut state = s;
+                let mut out = 0;
+                for i in (0..8).rev() {
+                    let b = (x >> i) & 1;
+                    out = (out << 1) | bit_output[2 * state + b];
+                    state = bit_next[2 * state + b];
+                }
+                byte_output[256 * s + x] = out;
+                byte_next[256 * s + x] = state;
+            }
+        }
+        StepTable {
+            byte_output,
+            byte_next,
+            bit_next,
+        }
+    }
+
+    /// Apply the transducer from `state` to the first `len` bits of `limb`,
+    /// most significant first. Returns the bits written, followed by zeros,
+    /// and the final state.
+    pub fn step_limb(&self, mut state: usize, limb: u64, len: usize) -> (u64, usize) {
+        assert!(len <= 64);
+        let mut res = 0;
+        let bytes = len / 8;
+        for j in 0..bytes {
+            let shift = 56 - 8 * j;
+            let i = 256 * state + ((limb >> shift) & 0xff) as usize;
+            res |= (self.byte_output[i] as u64) << shift;
+            state = self.byte_next[i];
+        }
+        let rest = len % 8;
+        if rest > 0 {
+            // The written byte is correct in its first `rest` bits, but the
+            // state must be followed bit by bit.
+            let shift = 56 - 8 * bytes;
+            let x = ((limb >> shift) & 0xff) as usize;
+            let out = self.byte_output[256 * state + x] & (0xff << (8 - rest));
+            res |= (out as u64) << shift;
+            for i in 0..rest {
+                state = self.bit_next[2 * state + ((x >> (7 - i)) & 1)];
+            }
+        }
+        return (res, state);
+    }
+
+    /// Apply the transducer to `word` in place, returning the final state.
+    pub fn step(&self, word: &mut PackedWord) -> usize {
+        let mut state = 0;
+        for (l, limb) in word.limbs.iter_mut().enumerate() {
+            (*limb, state) = self.step_limb(state, *limb, (word.len - 64 * l).min(64));
+        }
+        return state;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::transducer::AllTransducers;
+
+    #[test]
+    fn step_matches_transducer() {
+        let mut seed: u64 = 1;
+        for m in AllTransducers::new().take(3000).step_by(37) {
+            let table = m.step_table();
+            for len in [0, 8, 63, 64, 65, 130] {
+                let word: Vec<u8> = (0..len)
+                    .map(|_| {
+                        seed ^= seed << 13;
+                        seed ^= seed >> 7;
+                        seed ^= seed << 17;
+                        (seed & 1) as u8
+                    })
+                    .collect();
+                let mut packed = PackedWord::from_word(&word);
+                let mut expected = word.clone();
+                assert_eq!(table.step(&mut packed), m.step(&mut expected), "{:?}", m);
+                assert_eq!(packed.to_word(), expected, "{:?}", m);
+                assert_eq!(packed, PackedWord::from_word(&expected));
+            }
+        }
+    }
+}